@@ -1,11 +1,35 @@
-fn main() {
-    miye::run(update);
+use miye::glam::{Quat, Vec3};
+use miye::instances::InstanceId;
+use miye::state::State;
+
+#[derive(Default)]
+struct Cube {
+    spinning: Option<InstanceId>,
+    spin: f32,
 }
 
-fn update(state: &mut miye::state::State) {
-    if state.instances.is_empty() {
-        // The textures must be in the same folder as the model
-        state.add_instance("./models/cube.obj", miye::glam::Vec3::new(0.0, 0.0, 0.0));
-        state.add_instance("./models/cube.obj", miye::glam::Vec3::new(3.0, 0.0, 0.0));
+impl miye::MiyeApp for Cube {
+    fn init(&mut self, state: &mut State) {
+        // MTL texture paths are resolved relative to the model, then next to it
+        for position in [Vec3::new(0.0, 0.0, 0.0), Vec3::new(3.0, 0.0, 0.0)] {
+            match state.add_instance("./models/cube.obj", position) {
+                Ok(id) => self.spinning = Some(id),
+                Err(err) => eprintln!("{}", err),
+            }
+        }
+    }
+
+    fn update(&mut self, state: &mut State, dt: f32) {
+        self.spin += dt;
+        if let Some(instance) = self.spinning.and_then(|id| state.get_instance_mut(id)) {
+            instance.set_rotation(Quat::from_rotation_y(self.spin));
+        }
     }
 }
+
+fn main() -> miye::Result<()> {
+    miye::AppBuilder::new()
+        .title("Cube")
+        .size(1280, 720)
+        .run(Cube::default())
+}
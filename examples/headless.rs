@@ -0,0 +1,12 @@
+fn main() -> miye::Result<()> {
+    let mut state = miye::AppBuilder::new().build_headless(800, 600)?;
+    state.add_instance("./models/cube.obj", miye::glam::Vec3::new(0.0, 0.0, 0.0))?;
+    state.add_instance("./models/cube.obj", miye::glam::Vec3::new(3.0, 0.0, 0.0))?;
+    state.frame_selected();
+
+    let image = state.render_image()?;
+    image
+        .save("headless.png")
+        .expect("failed to save headless.png");
+    Ok(())
+}
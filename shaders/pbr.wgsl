@@ -0,0 +1,299 @@
+const PI: f32 = 3.14159265;
+const MAX_DIRECTIONAL_LIGHTS: u32 = 4u;
+const MAX_POINT_LIGHTS: u32 = 8u;
+
+struct CameraUniform {
+	view_proj: mat4x4<f32>,
+	view_position: vec4<f32>,
+}
+
+struct MaterialUniform {
+	base_color: vec4<f32>,
+	emissive: vec3<f32>,
+	metallic: f32,
+	roughness: f32,
+	normal_scale: f32,
+	occlusion_strength: f32,
+}
+
+struct DirectionalLight {
+	direction: vec3<f32>,
+	color: vec3<f32>,
+	intensity: f32,
+}
+
+struct PointLight {
+	position: vec3<f32>,
+	range: f32,
+	color: vec3<f32>,
+	intensity: f32,
+}
+
+struct LightsUniform {
+	ambient: vec3<f32>,
+	directional_count: u32,
+	point_count: u32,
+	environment_intensity: f32,
+	directional: array<DirectionalLight, MAX_DIRECTIONAL_LIGHTS>,
+	point: array<PointLight, MAX_POINT_LIGHTS>,
+}
+
+struct ShadowUniform {
+	view_proj: mat4x4<f32>,
+	bias: f32,
+	texel_size: f32,
+	enabled: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var base_color_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var base_color_sampler: sampler;
+@group(1) @binding(2)
+var metallic_roughness_texture: texture_2d<f32>;
+@group(1) @binding(3)
+var metallic_roughness_sampler: sampler;
+@group(1) @binding(4)
+var normal_texture: texture_2d<f32>;
+@group(1) @binding(5)
+var normal_sampler: sampler;
+@group(1) @binding(6)
+var occlusion_texture: texture_2d<f32>;
+@group(1) @binding(7)
+var occlusion_sampler: sampler;
+@group(1) @binding(8)
+var emissive_texture: texture_2d<f32>;
+@group(1) @binding(9)
+var emissive_sampler: sampler;
+@group(1) @binding(10)
+var<uniform> material: MaterialUniform;
+
+@group(2) @binding(0)
+var<uniform> lights: LightsUniform;
+@group(2) @binding(1)
+var environment: texture_2d<f32>;
+@group(2) @binding(2)
+var environment_sampler: sampler;
+
+@group(3) @binding(0)
+var shadow_map: texture_depth_2d;
+@group(3) @binding(1)
+var shadow_sampler: sampler_comparison;
+@group(3) @binding(2)
+var<uniform> shadow: ShadowUniform;
+
+struct VertexInput {
+	@location(0) position: vec3<f32>,
+	@location(1) tex_coords: vec2<f32>,
+	@location(2) normal: vec3<f32>,
+	@location(3) color: vec4<f32>,
+}
+
+struct InstanceInput {
+	@location(5) model_matrix_0: vec4<f32>,
+	@location(6) model_matrix_1: vec4<f32>,
+	@location(7) model_matrix_2: vec4<f32>,
+	@location(8) model_matrix_3: vec4<f32>,
+	@location(9) normal_matrix_0: vec3<f32>,
+	@location(10) normal_matrix_1: vec3<f32>,
+	@location(11) normal_matrix_2: vec3<f32>,
+}
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) tex_coords: vec2<f32>,
+	@location(1) world_position: vec3<f32>,
+	@location(2) world_normal: vec3<f32>,
+	@location(3) color: vec4<f32>,
+}
+
+@vertex
+fn vs(input: VertexInput, instance: InstanceInput) -> VertexOutput {
+	let model_matrix = mat4x4<f32>(
+		instance.model_matrix_0,
+		instance.model_matrix_1,
+		instance.model_matrix_2,
+		instance.model_matrix_3,
+	);
+	let normal_matrix = mat3x3<f32>(
+		instance.normal_matrix_0,
+		instance.normal_matrix_1,
+		instance.normal_matrix_2,
+	);
+	let world_position = model_matrix * vec4<f32>(input.position, 1.0);
+
+	var out: VertexOutput;
+	out.clip_position = camera.view_proj * world_position;
+	out.tex_coords = input.tex_coords;
+	out.world_position = world_position.xyz;
+	out.world_normal = normal_matrix * input.normal;
+	out.color = input.color;
+	return out;
+}
+
+// 3x3 PCF on top of the comparison sampler's own bilinear filtering.
+fn shadow_factor(world_position: vec3<f32>) -> f32 {
+	if shadow.enabled == 0u {
+		return 1.0;
+	}
+
+	let clip = shadow.view_proj * vec4<f32>(world_position, 1.0);
+	let ndc = clip.xyz / clip.w;
+	let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+	if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0 {
+		return 1.0;
+	}
+
+	var lit = 0.0;
+	for (var x = -1; x <= 1; x++) {
+		for (var y = -1; y <= 1; y++) {
+			let offset = vec2<f32>(f32(x), f32(y)) * shadow.texel_size;
+			lit += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, ndc.z - shadow.bias);
+		}
+	}
+	return lit / 9.0;
+}
+
+// Meshes carry no tangents, so the tangent frame comes from screen-space derivatives.
+fn perturb_normal(normal: vec3<f32>, position: vec3<f32>, uv: vec2<f32>, sample: vec3<f32>) -> vec3<f32> {
+	let dp1 = dpdx(position);
+	let dp2 = dpdy(position);
+	let duv1 = dpdx(uv);
+	let duv2 = dpdy(uv);
+
+	let dp2_perp = cross(dp2, normal);
+	let dp1_perp = cross(normal, dp1);
+	// The sign keeps the frame right-handed whichever way the screen's y axis points.
+	let handedness = sign(dot(dp1, dp2_perp));
+	let tangent = (dp2_perp * duv1.x + dp1_perp * duv2.x) * handedness;
+	// Texture coordinates grow downwards, while normal maps point green upwards.
+	let bitangent = -(dp2_perp * duv1.y + dp1_perp * duv2.y) * handedness;
+
+	let scale = max(dot(tangent, tangent), dot(bitangent, bitangent));
+	if scale <= 0.0 {
+		return normal;
+	}
+	let frame = mat3x3<f32>(tangent * inverseSqrt(scale), bitangent * inverseSqrt(scale), normal);
+	return normalize(frame * sample);
+}
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+	let a = roughness * roughness;
+	let a2 = a * a;
+	let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+	return a2 / (PI * d * d);
+}
+
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+	let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+	let g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+	let g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+	return g_v * g_l;
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+	return f0 + (1.0 - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+fn cook_torrance(
+	normal: vec3<f32>,
+	view_dir: vec3<f32>,
+	light_dir: vec3<f32>,
+	radiance: vec3<f32>,
+	albedo: vec3<f32>,
+	metallic: f32,
+	roughness: f32,
+	f0: vec3<f32>,
+) -> vec3<f32> {
+	let n_dot_l = dot(normal, light_dir);
+	if n_dot_l <= 0.0 {
+		return vec3<f32>(0.0);
+	}
+	let half_dir = normalize(view_dir + light_dir);
+	let n_dot_v = max(dot(normal, view_dir), 1e-4);
+	let n_dot_h = max(dot(normal, half_dir), 0.0);
+
+	let fresnel = fresnel_schlick(max(dot(half_dir, view_dir), 0.0), f0);
+	let specular = distribution_ggx(n_dot_h, roughness) * geometry_smith(n_dot_v, n_dot_l, roughness) * fresnel
+		/ (4.0 * n_dot_v * n_dot_l + 1e-4);
+	let diffuse = (1.0 - fresnel) * (1.0 - metallic) * albedo / PI;
+	// Scaled by PI so a light is as bright here as it is on a Phong material.
+	return (diffuse + specular) * radiance * n_dot_l * PI;
+}
+
+fn sample_environment(direction: vec3<f32>, lod: f32) -> vec3<f32> {
+	let uv = vec2<f32>(
+		atan2(direction.z, direction.x) / (2.0 * PI) + 0.5,
+		acos(clamp(direction.y, -1.0, 1.0)) / PI,
+	);
+	return textureSampleLevel(environment, environment_sampler, uv, lod).rgb;
+}
+
+// Karis' analytic fit of the split-sum BRDF lookup table.
+fn environment_brdf(f0: vec3<f32>, roughness: f32, n_dot_v: f32) -> vec3<f32> {
+	let c0 = vec4<f32>(-1.0, -0.0275, -0.572, 0.022);
+	let c1 = vec4<f32>(1.0, 0.0425, 1.04, -0.04);
+	let r = roughness * c0 + c1;
+	let a004 = min(r.x * r.x, exp2(-9.28 * n_dot_v)) * r.x + r.y;
+	let ab = vec2<f32>(-1.04, 1.04) * a004 + r.zw;
+	return f0 * ab.x + ab.y;
+}
+
+@fragment
+fn fs(in: VertexOutput) -> @location(0) vec4f {
+	let base_color = textureSample(base_color_texture, base_color_sampler, in.tex_coords) * in.color * material.base_color;
+	let metallic_roughness = textureSample(metallic_roughness_texture, metallic_roughness_sampler, in.tex_coords);
+	let normal_sample = textureSample(normal_texture, normal_sampler, in.tex_coords).xyz * 2.0 - 1.0;
+	let occlusion_sample = textureSample(occlusion_texture, occlusion_sampler, in.tex_coords).r;
+	let emissive = textureSample(emissive_texture, emissive_sampler, in.tex_coords).rgb * material.emissive;
+
+	let metallic = saturate(material.metallic * metallic_roughness.b);
+	let roughness = clamp(material.roughness * metallic_roughness.g, 0.04, 1.0);
+	let occlusion = mix(1.0, occlusion_sample, material.occlusion_strength);
+	let albedo = base_color.rgb;
+	let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+	let view_dir = normalize(camera.view_position.xyz - in.world_position);
+
+	var geometric_normal = normalize(in.world_normal);
+	// Models are drawn without culling, so light back faces as seen by the camera.
+	if dot(geometric_normal, view_dir) < 0.0 {
+		geometric_normal = -geometric_normal;
+	}
+	let tangent_normal = vec3<f32>(normal_sample.xy * material.normal_scale, normal_sample.z);
+	let normal = perturb_normal(geometric_normal, in.world_position, in.tex_coords, tangent_normal);
+	let n_dot_v = max(dot(normal, view_dir), 1e-4);
+
+	// The environment's small mips stand in for prefiltered irradiance and glossy reflections.
+	let blurriest = max(f32(textureNumLevels(environment)) - 3.0, 0.0);
+	let irradiance = sample_environment(normal, blurriest);
+	let reflection = sample_environment(reflect(-view_dir, normal), roughness * blurriest);
+	let ambient_diffuse = irradiance * albedo * (1.0 - metallic);
+	let ambient_specular = reflection * environment_brdf(f0, roughness, n_dot_v);
+	var color = (ambient_diffuse + ambient_specular) * lights.environment_intensity * occlusion;
+
+	let shadowed = shadow_factor(in.world_position);
+
+	for (var i = 0u; i < min(lights.directional_count, MAX_DIRECTIONAL_LIGHTS); i++) {
+		let light = lights.directional[i];
+		var radiance = light.color * light.intensity;
+		if i == 0u {
+			radiance *= shadowed;
+		}
+		color += cook_torrance(normal, view_dir, -light.direction, radiance, albedo, metallic, roughness, f0);
+	}
+
+	for (var i = 0u; i < min(lights.point_count, MAX_POINT_LIGHTS); i++) {
+		let light = lights.point[i];
+		let to_light = light.position - in.world_position;
+		let distance = length(to_light);
+		let falloff = saturate(1.0 - pow(distance / light.range, 4.0));
+		let attenuation = falloff * falloff / (distance * distance + 1.0);
+		let radiance = light.color * light.intensity * attenuation;
+		color += cook_torrance(normal, view_dir, to_light / distance, radiance, albedo, metallic, roughness, f0);
+	}
+
+	return vec4<f32>(color + emissive, base_color.a);
+}
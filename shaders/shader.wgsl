@@ -1,5 +1,45 @@
+const MAX_DIRECTIONAL_LIGHTS: u32 = 4u;
+const MAX_POINT_LIGHTS: u32 = 8u;
+
 struct CameraUniform {
 	view_proj: mat4x4<f32>,
+	view_position: vec4<f32>,
+}
+
+struct MaterialUniform {
+	ambient: vec3<f32>,
+	diffuse: vec3<f32>,
+	specular: vec3<f32>,
+	shininess: f32,
+}
+
+struct DirectionalLight {
+	direction: vec3<f32>,
+	color: vec3<f32>,
+	intensity: f32,
+}
+
+struct PointLight {
+	position: vec3<f32>,
+	range: f32,
+	color: vec3<f32>,
+	intensity: f32,
+}
+
+struct LightsUniform {
+	ambient: vec3<f32>,
+	directional_count: u32,
+	point_count: u32,
+	environment_intensity: f32,
+	directional: array<DirectionalLight, MAX_DIRECTIONAL_LIGHTS>,
+	point: array<PointLight, MAX_POINT_LIGHTS>,
+}
+
+struct ShadowUniform {
+	view_proj: mat4x4<f32>,
+	bias: f32,
+	texel_size: f32,
+	enabled: u32,
 }
 
 @group(0) @binding(0)
@@ -9,27 +49,140 @@ var<uniform> camera: CameraUniform;
 var texture: texture_2d<f32>;
 @group(1) @binding(1)
 var smpler: sampler;
+@group(1) @binding(2)
+var<uniform> material: MaterialUniform;
+
+@group(2) @binding(0)
+var<uniform> lights: LightsUniform;
+
+@group(3) @binding(0)
+var shadow_map: texture_depth_2d;
+@group(3) @binding(1)
+var shadow_sampler: sampler_comparison;
+@group(3) @binding(2)
+var<uniform> shadow: ShadowUniform;
 
 struct VertexInput {
 	@location(0) position: vec3<f32>,
 	@location(1) tex_coords: vec2<f32>,
+	@location(2) normal: vec3<f32>,
+	@location(3) color: vec4<f32>,
+}
+
+struct InstanceInput {
+	@location(5) model_matrix_0: vec4<f32>,
+	@location(6) model_matrix_1: vec4<f32>,
+	@location(7) model_matrix_2: vec4<f32>,
+	@location(8) model_matrix_3: vec4<f32>,
+	@location(9) normal_matrix_0: vec3<f32>,
+	@location(10) normal_matrix_1: vec3<f32>,
+	@location(11) normal_matrix_2: vec3<f32>,
 }
 
 struct VertexOutput {
 	@builtin(position) clip_position: vec4<f32>,
 	@location(0) tex_coords: vec2<f32>,
+	@location(1) world_position: vec3<f32>,
+	@location(2) world_normal: vec3<f32>,
+	@location(3) color: vec4<f32>,
 }
 
 @vertex
-fn vs(input: VertexInput) -> VertexOutput {
-	
+fn vs(input: VertexInput, instance: InstanceInput) -> VertexOutput {
+	let model_matrix = mat4x4<f32>(
+		instance.model_matrix_0,
+		instance.model_matrix_1,
+		instance.model_matrix_2,
+		instance.model_matrix_3,
+	);
+	let normal_matrix = mat3x3<f32>(
+		instance.normal_matrix_0,
+		instance.normal_matrix_1,
+		instance.normal_matrix_2,
+	);
+	let world_position = model_matrix * vec4<f32>(input.position, 1.0);
+
 	var out: VertexOutput;
-	out.clip_position = camera.view_proj * vec4<f32>(input.position, 1.0);
+	out.clip_position = camera.view_proj * world_position;
 	out.tex_coords = input.tex_coords;
+	out.world_position = world_position.xyz;
+	out.world_normal = normal_matrix * input.normal;
+	out.color = input.color;
 	return out;
 }
 
+fn blinn_phong(
+	normal: vec3<f32>,
+	view_dir: vec3<f32>,
+	light_dir: vec3<f32>,
+	radiance: vec3<f32>,
+	albedo: vec3<f32>,
+) -> vec3<f32> {
+	let diffuse = max(dot(normal, light_dir), 0.0) * albedo;
+	let half_dir = normalize(view_dir + light_dir);
+	var specular = vec3<f32>(0.0);
+	if dot(normal, light_dir) > 0.0 {
+		specular = pow(max(dot(normal, half_dir), 0.0), material.shininess) * material.specular;
+	}
+	return (diffuse + specular) * radiance;
+}
+
+// 3x3 PCF on top of the comparison sampler's own bilinear filtering.
+fn shadow_factor(world_position: vec3<f32>) -> f32 {
+	if shadow.enabled == 0u {
+		return 1.0;
+	}
+
+	let clip = shadow.view_proj * vec4<f32>(world_position, 1.0);
+	let ndc = clip.xyz / clip.w;
+	let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+	if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0 {
+		return 1.0;
+	}
+
+	var lit = 0.0;
+	for (var x = -1; x <= 1; x++) {
+		for (var y = -1; y <= 1; y++) {
+			let offset = vec2<f32>(f32(x), f32(y)) * shadow.texel_size;
+			lit += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, ndc.z - shadow.bias);
+		}
+	}
+	return lit / 9.0;
+}
+
 @fragment
 fn fs(in: VertexOutput) -> @location(0) vec4f {
-	return textureSample(texture, smpler, in.tex_coords);
+	let sample = textureSample(texture, smpler, in.tex_coords) * in.color;
+	let albedo = sample.rgb * material.diffuse;
+	let view_dir = normalize(camera.view_position.xyz - in.world_position);
+
+	var normal = normalize(in.world_normal);
+	// Models are drawn without culling, so light back faces as seen by the camera.
+	if dot(normal, view_dir) < 0.0 {
+		normal = -normal;
+	}
+
+	var color = lights.ambient * material.ambient * sample.rgb;
+	let shadowed = shadow_factor(in.world_position);
+
+	for (var i = 0u; i < min(lights.directional_count, MAX_DIRECTIONAL_LIGHTS); i++) {
+		let light = lights.directional[i];
+		var radiance = light.color * light.intensity;
+		if i == 0u {
+			radiance *= shadowed;
+		}
+		color += blinn_phong(normal, view_dir, -light.direction, radiance, albedo);
+	}
+
+	for (var i = 0u; i < min(lights.point_count, MAX_POINT_LIGHTS); i++) {
+		let light = lights.point[i];
+		let to_light = light.position - in.world_position;
+		let distance = length(to_light);
+		let falloff = saturate(1.0 - pow(distance / light.range, 4.0));
+		let attenuation = falloff * falloff / (distance * distance + 1.0);
+		let radiance = light.color * light.intensity * attenuation;
+		color += blinn_phong(normal, view_dir, to_light / distance, radiance, albedo);
+	}
+
+	return vec4<f32>(color, sample.a);
 }
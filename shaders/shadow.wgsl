@@ -0,0 +1,28 @@
+struct ShadowUniform {
+	view_proj: mat4x4<f32>,
+	bias: f32,
+	texel_size: f32,
+	enabled: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> shadow: ShadowUniform;
+
+struct InstanceInput {
+	@location(5) model_matrix_0: vec4<f32>,
+	@location(6) model_matrix_1: vec4<f32>,
+	@location(7) model_matrix_2: vec4<f32>,
+	@location(8) model_matrix_3: vec4<f32>,
+}
+
+// Depth only, so the light's view needs nothing but positions.
+@vertex
+fn vs(@location(0) position: vec3<f32>, instance: InstanceInput) -> @builtin(position) vec4<f32> {
+	let model_matrix = mat4x4<f32>(
+		instance.model_matrix_0,
+		instance.model_matrix_1,
+		instance.model_matrix_2,
+		instance.model_matrix_3,
+	);
+	return shadow.view_proj * model_matrix * vec4<f32>(position, 1.0);
+}
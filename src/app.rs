@@ -1,22 +1,143 @@
+use crate::config::{AdapterFallback, Config};
 use crate::renderer;
+use crate::shadows::ShadowSettings;
 use crate::state::State;
-use winit::event::WindowEvent;
-use winit::{application::ApplicationHandler, window::WindowAttributes};
+use winit::application::ApplicationHandler;
+use winit::event::{DeviceEvent, WindowEvent};
 
-pub enum App {
-    Uninitialized(fn(&mut State) -> ()),
-    Initialized(State),
+pub trait MiyeApp {
+    fn init(&mut self, _state: &mut State) {}
+
+    fn update(&mut self, _state: &mut State, _dt: f32) {}
+
+    fn on_event(&mut self, _state: &mut State, _event: &WindowEvent) {}
+}
+
+#[derive(Default)]
+pub struct AppBuilder {
+    config: Config,
+}
+
+impl AppBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.config.title = title.into();
+        self
+    }
+
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.config.size = Some((width, height));
+        self
+    }
+
+    pub fn fullscreen(mut self, fullscreen: bool) -> Self {
+        self.config.fullscreen = fullscreen;
+        self
+    }
+
+    pub fn present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+        self.config.present_mode = present_mode;
+        self
+    }
+
+    pub fn vsync(self, vsync: bool) -> Self {
+        self.present_mode(if vsync {
+            wgpu::PresentMode::AutoVsync
+        } else {
+            wgpu::PresentMode::AutoNoVsync
+        })
+    }
+
+    pub fn clear_color(mut self, clear_color: wgpu::Color) -> Self {
+        self.config.clear_color = clear_color;
+        self
+    }
+
+    pub fn backends(mut self, backends: wgpu::Backends) -> Self {
+        self.config.backends = backends;
+        self
+    }
+
+    pub fn power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+        self.config.power_preference = power_preference;
+        self
+    }
+
+    pub fn adapter_fallback(mut self, adapter_fallback: AdapterFallback) -> Self {
+        self.config.adapter_fallback = adapter_fallback;
+        self
+    }
+
+    // Lowered to the closest count the adapter supports.
+    pub fn sample_count(mut self, sample_count: u32) -> Self {
+        self.config.sample_count = sample_count;
+        self
+    }
+
+    pub fn shadows(mut self, shadows: ShadowSettings) -> Self {
+        self.config.shadows = shadows;
+        self
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    // Returns once the window is closed, or with the error that stopped the app.
+    pub fn run<A: MiyeApp>(self, app: A) -> crate::Result<()> {
+        let event_loop = winit::event_loop::EventLoop::new().map_err(crate::Error::EventLoop)?;
+        let mut app = App {
+            app,
+            config: self.config,
+            state: None,
+            paused: false,
+            error: None,
+        };
+        event_loop
+            .run_app(&mut app)
+            .map_err(crate::Error::EventLoop)?;
+        app.error.map_or(Ok(()), Err)
+    }
+
+    pub fn build_headless(self, width: u32, height: u32) -> crate::Result<State> {
+        State::new_headless(width, height, &self.config)
+    }
+}
+
+struct App<A> {
+    app: A,
+    config: Config,
+    state: Option<Box<State>>,
+    paused: bool,
+    // Set when the app stops because of an error, and returned from `run`.
+    error: Option<crate::Error>,
+}
+
+impl<A> App<A> {
+    fn fail(&mut self, event_loop: &winit::event_loop::ActiveEventLoop, err: crate::Error) {
+        log::error!("{}", err);
+        self.error = Some(err);
+        event_loop.exit();
+    }
 }
 
-impl ApplicationHandler for App {
+impl<A: MiyeApp> ApplicationHandler for App<A> {
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
-        if let App::Uninitialized(f) = self {
-            let window = event_loop
-                .create_window(WindowAttributes::default())
-                .unwrap();
-            let state = State::new(window, *f);
+        if self.state.is_none() {
+            let state = event_loop
+                .create_window(self.config.window_attributes())
+                .map_err(crate::Error::CreateWindow)
+                .and_then(|window| State::new(window, &self.config));
+            let mut state = match state {
+                Ok(state) => Box::new(state),
+                Err(err) => return self.fail(event_loop, err),
+            };
+            self.app.init(&mut state);
 
-            *self = App::Initialized(state);
+            self.state = Some(state);
         }
     }
 
@@ -26,33 +147,69 @@ impl ApplicationHandler for App {
         _window_id: winit::window::WindowId,
         event: winit::event::WindowEvent,
     ) {
-        if let App::Initialized(state) = self {
+        if let Some(state) = &mut self.state {
+            self.app.on_event(state, &event);
+
             match event {
                 WindowEvent::CloseRequested => {
                     event_loop.exit();
                 }
                 WindowEvent::RedrawRequested => {
-                    state.window.request_redraw();
-                    state.update();
-                    renderer::draw(state);
+                    // Stop requesting frames until the window has an area again.
+                    if state.is_minimized() {
+                        self.paused = true;
+                        return;
+                    }
+                    if let Some(window) = &state.window {
+                        window.request_redraw();
+                    }
+                    state.begin_frame();
+                    let dt = state.delta_time;
+                    self.app.update(state, dt);
+                    state.end_frame();
+                    state.instances.update_world_transforms();
+                    if let Err(err) = renderer::draw(state) {
+                        self.fail(event_loop, err);
+                    }
                 }
                 WindowEvent::Resized(size) => {
-                    state.surface_config.width = size.width;
-                    state.surface_config.height = size.height;
-                    state
-                        .surface
-                        .configure(&state.device, &state.surface_config);
-                    state.depth_texture = crate::texture::Texture::create_depth_texture(
-                        &state.surface_config,
-                        &state.device,
-                    );
-                    state.camera.reconfigure_aspect_ratio(&state.window);
+                    state.resize(size.width, size.height);
+                    if self.paused && size.width > 0 && size.height > 0 {
+                        self.paused = false;
+                        // Don't count the time spent minimized as one long frame.
+                        state.last_frame = std::time::Instant::now();
+                        if let Some(window) = &state.window {
+                            window.request_redraw();
+                        }
+                    }
                 }
                 WindowEvent::KeyboardInput { event, .. } => {
                     crate::input::handle_keyboard_event(event, state)
                 }
+                WindowEvent::MouseInput {
+                    state: button_state,
+                    button,
+                    ..
+                } => crate::input::handle_mouse_input(button, button_state, state),
+                WindowEvent::MouseWheel { delta, .. } => {
+                    crate::input::handle_mouse_wheel(delta, state)
+                }
+                WindowEvent::Focused(false) => state.input.clear(),
                 _ => (),
             };
         }
     }
+
+    fn device_event(
+        &mut self,
+        _event_loop: &winit::event_loop::ActiveEventLoop,
+        _device_id: winit::event::DeviceId,
+        event: DeviceEvent,
+    ) {
+        if let Some(state) = &mut self.state {
+            if let DeviceEvent::MouseMotion { delta } = event {
+                crate::input::handle_mouse_motion(delta, state);
+            }
+        }
+    }
 }
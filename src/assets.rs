@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+
+use crate::models::Model;
+use crate::resources::{FileResolver, ResourceResolver};
+
+// Models are held weakly so their GPU buffers are freed once nothing else uses them.
+pub struct AssetCache {
+    models: HashMap<PathBuf, Weak<Model>>,
+    resolver: Box<dyn ResourceResolver>,
+}
+
+impl Default for AssetCache {
+    fn default() -> Self {
+        Self::with_resolver(FileResolver::default())
+    }
+}
+
+impl fmt::Debug for AssetCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AssetCache")
+            .field("models", &self.models)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AssetCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_resolver(resolver: impl ResourceResolver + 'static) -> Self {
+        AssetCache {
+            models: HashMap::new(),
+            resolver: Box::new(resolver),
+        }
+    }
+
+    // Cached models came from the old resolver, so they are forgotten.
+    pub fn set_resolver(&mut self, resolver: impl ResourceResolver + 'static) {
+        self.resolver = Box::new(resolver);
+        self.models.clear();
+    }
+
+    pub fn load_model(
+        &mut self,
+        file_path: &str,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> crate::Result<Arc<Model>> {
+        let key = self.cache_key(file_path);
+        if let Some(model) = self.models.get(&key).and_then(Weak::upgrade) {
+            return Ok(model);
+        }
+
+        let model = Arc::new(crate::models::load_model_with(
+            file_path,
+            self.resolver.as_ref(),
+            device,
+            queue,
+        )?);
+        self.insert(key, &model);
+        Ok(model)
+    }
+
+    // Replaces any model cached under the same path, so later lookups see the new bytes.
+    pub fn load_model_from_bytes(
+        &mut self,
+        file_path: &str,
+        bytes: &[u8],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> crate::Result<Arc<Model>> {
+        let model = Arc::new(crate::models::load_model_from_bytes(
+            file_path,
+            bytes,
+            self.resolver.as_ref(),
+            device,
+            queue,
+        )?);
+        self.insert(self.cache_key(file_path), &model);
+        Ok(model)
+    }
+
+    pub fn get_model(&self, file_path: &str) -> Option<Arc<Model>> {
+        self.models
+            .get(&self.cache_key(file_path))
+            .and_then(Weak::upgrade)
+    }
+
+    pub fn model_path(&self, model: &Arc<Model>) -> Option<&Path> {
+        self.models
+            .iter()
+            .find(|(_, cached)| std::ptr::eq(cached.as_ptr(), Arc::as_ptr(model)))
+            .map(|(path, _)| path.as_path())
+    }
+
+    pub fn len(&self) -> usize {
+        self.models
+            .values()
+            .filter(|model| model.strong_count() > 0)
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.models.clear();
+    }
+
+    fn cache_key(&self, file_path: &str) -> PathBuf {
+        self.resolver.cache_key(Path::new(file_path))
+    }
+
+    fn insert(&mut self, key: PathBuf, model: &Arc<Model>) {
+        self.models.retain(|_, model| model.strong_count() > 0);
+        self.models.insert(key, Arc::downgrade(model));
+    }
+}
@@ -0,0 +1,62 @@
+use crate::shadows::ShadowSettings;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum AdapterFallback {
+    // Use a hardware adapter when there is one, otherwise a software one.
+    #[default]
+    Auto,
+    Never,
+    Always,
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub title: String,
+    pub size: Option<(u32, u32)>,
+    pub fullscreen: bool,
+    pub present_mode: wgpu::PresentMode,
+    pub clear_color: wgpu::Color,
+    pub backends: wgpu::Backends,
+    pub power_preference: wgpu::PowerPreference,
+    pub adapter_fallback: AdapterFallback,
+    pub sample_count: u32,
+    pub shadows: ShadowSettings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            title: "miye".to_string(),
+            size: None,
+            fullscreen: false,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            clear_color: wgpu::Color::WHITE,
+            backends: wgpu::Backends::all(),
+            power_preference: wgpu::PowerPreference::default(),
+            adapter_fallback: AdapterFallback::Auto,
+            sample_count: 4,
+            shadows: ShadowSettings::default(),
+        }
+    }
+}
+
+impl Config {
+    pub(crate) fn create_instance(&self) -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: self.backends,
+            ..Default::default()
+        })
+    }
+
+    pub(crate) fn window_attributes(&self) -> winit::window::WindowAttributes {
+        let mut attributes = winit::window::WindowAttributes::default().with_title(&self.title);
+        if let Some((width, height)) = self.size {
+            attributes = attributes.with_inner_size(winit::dpi::LogicalSize::new(width, height));
+        }
+        if self.fullscreen {
+            attributes =
+                attributes.with_fullscreen(Some(winit::window::Fullscreen::Borderless(None)));
+        }
+        attributes
+    }
+}
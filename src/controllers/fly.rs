@@ -0,0 +1,39 @@
+use super::{movement_axis, CameraController};
+use crate::input::InputState;
+use crate::instances::camera::{Camera, CameraMode};
+
+pub struct FlyController {
+    pub speed: f32,
+    pub sensitivity: f32,
+}
+
+impl Default for FlyController {
+    fn default() -> Self {
+        FlyController {
+            speed: 5.0,
+            sensitivity: 0.1,
+        }
+    }
+}
+
+impl CameraController for FlyController {
+    fn activate(&mut self, camera: &mut Camera) {
+        camera.set_mode(CameraMode::Fly);
+    }
+
+    fn update(&mut self, camera: &mut Camera, input: &InputState, dt: f32) {
+        let movement = movement_axis(input, camera.forward(), camera.right());
+        camera.apply_movement(movement.normalize_or_zero() * self.speed * dt);
+    }
+
+    fn mouse_motion(&mut self, camera: &mut Camera, delta: (f64, f64)) {
+        camera.rotate(
+            delta.0 as f32 * self.sensitivity,
+            -delta.1 as f32 * self.sensitivity,
+        );
+    }
+
+    fn grabs_cursor(&self) -> bool {
+        true
+    }
+}
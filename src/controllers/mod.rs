@@ -0,0 +1,52 @@
+pub mod fly;
+pub mod orbit;
+
+pub use fly::FlyController;
+pub use orbit::OrbitController;
+
+use glam::Vec3;
+use winit::event::{ElementState, MouseButton};
+use winit::keyboard::KeyCode;
+
+use crate::input::InputState;
+use crate::instances::camera::Camera;
+use crate::models::Bounds;
+
+pub trait CameraController {
+    fn activate(&mut self, _camera: &mut Camera) {}
+
+    fn keyboard_input(&mut self, _camera: &mut Camera, _code: KeyCode, _state: ElementState) {}
+
+    fn mouse_input(&mut self, _camera: &mut Camera, _button: MouseButton, _state: ElementState) {}
+
+    fn mouse_motion(&mut self, _camera: &mut Camera, _delta: (f64, f64)) {}
+
+    fn mouse_wheel(&mut self, _camera: &mut Camera, _lines: f32) {}
+
+    fn update(&mut self, _camera: &mut Camera, _input: &InputState, _dt: f32) {}
+
+    fn frame(&mut self, camera: &mut Camera, bounds: Bounds) {
+        camera.frame(bounds.center(), bounds.radius());
+    }
+
+    fn grabs_cursor(&self) -> bool {
+        false
+    }
+}
+
+// WASD moves along the view and Q/E lowers and raises. Not normalized.
+pub fn movement_axis(input: &InputState, forward: Vec3, right: Vec3) -> Vec3 {
+    let bindings = [
+        (KeyCode::KeyW, forward),
+        (KeyCode::KeyA, -right),
+        (KeyCode::KeyS, -forward),
+        (KeyCode::KeyD, right),
+        (KeyCode::KeyE, Vec3::Y),
+        (KeyCode::KeyQ, -Vec3::Y),
+    ];
+    bindings
+        .into_iter()
+        .filter(|&(code, _)| input.is_key_held(code))
+        .map(|(_, direction)| direction)
+        .sum()
+}
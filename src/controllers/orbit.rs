@@ -0,0 +1,64 @@
+use winit::event::{ElementState, MouseButton};
+
+use super::{movement_axis, CameraController};
+use crate::input::InputState;
+use crate::instances::camera::{Camera, CameraMode};
+
+pub struct OrbitController {
+    pub rotate_speed: f32,
+    pub pan_speed: f32,
+    pub zoom_speed: f32,
+    // In multiples of the distance to the target per second.
+    pub move_speed: f32,
+    rotating: bool,
+    panning: bool,
+}
+
+impl Default for OrbitController {
+    fn default() -> Self {
+        OrbitController {
+            rotate_speed: 0.3,
+            pan_speed: 0.002,
+            zoom_speed: 0.1,
+            move_speed: 1.0,
+            rotating: false,
+            panning: false,
+        }
+    }
+}
+
+impl CameraController for OrbitController {
+    fn activate(&mut self, camera: &mut Camera) {
+        camera.set_mode(CameraMode::LookAt);
+        self.rotating = false;
+        self.panning = false;
+    }
+
+    // Moves the target along with the camera, so it stays in front.
+    fn update(&mut self, camera: &mut Camera, input: &InputState, dt: f32) {
+        let movement = movement_axis(input, camera.forward(), camera.right());
+        camera.translate(movement.normalize_or_zero() * self.move_speed * camera.distance() * dt);
+    }
+
+    fn mouse_input(&mut self, _camera: &mut Camera, button: MouseButton, state: ElementState) {
+        let pressed = state == ElementState::Pressed;
+        match button {
+            MouseButton::Left => self.rotating = pressed,
+            MouseButton::Middle => self.panning = pressed,
+            _ => {}
+        }
+    }
+
+    fn mouse_motion(&mut self, camera: &mut Camera, delta: (f64, f64)) {
+        let (dx, dy) = (delta.0 as f32, delta.1 as f32);
+        if self.rotating {
+            camera.orbit(dx * self.rotate_speed, dy * self.rotate_speed);
+        } else if self.panning {
+            camera.pan(-dx * self.pan_speed, dy * self.pan_speed);
+        }
+    }
+
+    fn mouse_wheel(&mut self, camera: &mut Camera, lines: f32) {
+        camera.dolly((1.0 - self.zoom_speed).powf(lines));
+    }
+}
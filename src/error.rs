@@ -0,0 +1,178 @@
+use std::fmt;
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        source: tobj::LoadError,
+    },
+    Gltf {
+        path: PathBuf,
+        source: gltf::Error,
+    },
+    UnsupportedFormat {
+        path: PathBuf,
+    },
+    InvalidModel {
+        path: PathBuf,
+        message: String,
+    },
+    MissingMaterial {
+        path: PathBuf,
+        material: usize,
+    },
+    MissingTexture {
+        path: PathBuf,
+        material: String,
+        texture: PathBuf,
+        searched: Vec<PathBuf>,
+    },
+    SceneParse {
+        path: PathBuf,
+        source: ron::error::SpannedError,
+    },
+    SceneWrite {
+        path: PathBuf,
+        source: ron::Error,
+    },
+    ImageDecode(image::ImageError),
+    ImageSave {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    EventLoop(winit::error::EventLoopError),
+    CreateWindow(winit::error::OsError),
+    NoAdapter,
+    UnsupportedSurface,
+    InvalidSize {
+        width: u32,
+        height: u32,
+    },
+    UnsupportedReadback(wgpu::TextureFormat),
+    CreateSurface(wgpu::CreateSurfaceError),
+    Surface(wgpu::SurfaceError),
+    RequestDevice(wgpu::RequestDeviceError),
+    BufferMap(wgpu::BufferAsyncError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => {
+                write!(f, "failed to read {}: {}", path.display(), source)
+            }
+            Error::Parse { path, source } => {
+                write!(f, "failed to parse {}: {}", path.display(), source)
+            }
+            Error::Gltf { path, source } => {
+                write!(f, "failed to import {}: {}", path.display(), source)
+            }
+            Error::UnsupportedFormat { path } => {
+                write!(f, "{} is not a supported model format", path.display())
+            }
+            Error::InvalidModel { path, message } => {
+                write!(f, "failed to parse {}: {}", path.display(), message)
+            }
+            Error::MissingMaterial { path, material } => {
+                write!(
+                    f,
+                    "{} references missing material {}",
+                    path.display(),
+                    material
+                )
+            }
+            Error::MissingTexture {
+                path,
+                material,
+                texture,
+                searched,
+            } => {
+                write!(
+                    f,
+                    "texture {} for material {:?} in {} was not found",
+                    texture.display(),
+                    material,
+                    path.display()
+                )?;
+                let mut separator = " (searched ";
+                for candidate in searched {
+                    write!(f, "{}{}", separator, candidate.display())?;
+                    separator = ", ";
+                }
+                if !searched.is_empty() {
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+            Error::SceneParse { path, source } => {
+                write!(f, "failed to parse scene {}: {}", path.display(), source)
+            }
+            Error::SceneWrite { path, source } => {
+                write!(f, "failed to write scene {}: {}", path.display(), source)
+            }
+            Error::ImageDecode(source) => write!(f, "failed to decode image: {}", source),
+            Error::ImageSave { path, source } => {
+                write!(f, "failed to save {}: {}", path.display(), source)
+            }
+            Error::EventLoop(source) => write!(f, "event loop failed: {}", source),
+            Error::CreateWindow(source) => write!(f, "failed to create window: {}", source),
+            Error::NoAdapter => write!(f, "no compatible graphics adapter was found"),
+            Error::UnsupportedSurface => {
+                write!(f, "the graphics adapter cannot present to this window")
+            }
+            Error::InvalidSize { width, height } => {
+                write!(f, "cannot render to a {}x{} target", width, height)
+            }
+            Error::UnsupportedReadback(format) => {
+                write!(f, "cannot read back {:?} textures as RGBA8 images", format)
+            }
+            Error::CreateSurface(source) => write!(f, "failed to create surface: {}", source),
+            Error::Surface(source) => write!(f, "failed to acquire surface texture: {}", source),
+            Error::RequestDevice(source) => {
+                write!(f, "failed to request graphics device: {}", source)
+            }
+            Error::BufferMap(source) => write!(f, "failed to read back GPU buffer: {}", source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Parse { source, .. } => Some(source),
+            Error::Gltf { source, .. } => Some(source),
+            Error::SceneParse { source, .. } => Some(source),
+            Error::SceneWrite { source, .. } => Some(source),
+            Error::ImageDecode(source) => Some(source),
+            Error::ImageSave { source, .. } => Some(source),
+            Error::EventLoop(source) => Some(source),
+            Error::CreateWindow(source) => Some(source),
+            Error::CreateSurface(source) => Some(source),
+            Error::Surface(source) => Some(source),
+            Error::RequestDevice(source) => Some(source),
+            Error::BufferMap(source) => Some(source),
+            Error::UnsupportedFormat { .. }
+            | Error::InvalidModel { .. }
+            | Error::MissingMaterial { .. }
+            | Error::MissingTexture { .. }
+            | Error::NoAdapter
+            | Error::UnsupportedSurface
+            | Error::InvalidSize { .. }
+            | Error::UnsupportedReadback(_) => None,
+        }
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(err: image::ImageError) -> Self {
+        Error::ImageDecode(err)
+    }
+}
@@ -1,8 +1,92 @@
-use winit::event::ElementState;
+use std::collections::HashSet;
+
+use winit::event::{ElementState, MouseButton, MouseScrollDelta};
 use winit::keyboard::KeyCode;
+use winit::window::CursorGrabMode;
 use winit::{event::KeyEvent, keyboard::PhysicalKey};
 
-use glam::Vec3;
+use crate::controllers::{FlyController, OrbitController};
+use crate::instances::camera::CameraMode;
+
+const SCREENSHOT_KEY: KeyCode = KeyCode::F12;
+const CAMERA_MODE_KEY: KeyCode = KeyCode::Tab;
+const FRAME_KEY: KeyCode = KeyCode::KeyF;
+const PIXELS_PER_LINE: f32 = 40.0;
+
+#[derive(Debug, Default)]
+pub struct InputState {
+    keys_held: HashSet<KeyCode>,
+    keys_pressed: HashSet<KeyCode>,
+    buttons_held: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    mouse_delta: glam::Vec2,
+    scroll_lines: f32,
+}
+
+impl InputState {
+    pub fn is_key_held(&self, code: KeyCode) -> bool {
+        self.keys_held.contains(&code)
+    }
+
+    pub fn was_key_pressed(&self, code: KeyCode) -> bool {
+        self.keys_pressed.contains(&code)
+    }
+
+    pub fn is_button_held(&self, button: MouseButton) -> bool {
+        self.buttons_held.contains(&button)
+    }
+
+    pub fn was_button_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    pub fn mouse_delta(&self) -> glam::Vec2 {
+        self.mouse_delta
+    }
+
+    pub fn scroll_lines(&self) -> f32 {
+        self.scroll_lines
+    }
+
+    pub fn clear(&mut self) {
+        self.keys_held.clear();
+        self.buttons_held.clear();
+        self.end_frame();
+    }
+
+    pub(crate) fn end_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.buttons_pressed.clear();
+        self.mouse_delta = glam::Vec2::ZERO;
+        self.scroll_lines = 0.0;
+    }
+
+    fn key_input(&mut self, code: KeyCode, state: ElementState) {
+        match state {
+            ElementState::Pressed => {
+                if self.keys_held.insert(code) {
+                    self.keys_pressed.insert(code);
+                }
+            }
+            ElementState::Released => {
+                self.keys_held.remove(&code);
+            }
+        }
+    }
+
+    fn mouse_input(&mut self, button: MouseButton, state: ElementState) {
+        match state {
+            ElementState::Pressed => {
+                if self.buttons_held.insert(button) {
+                    self.buttons_pressed.insert(button);
+                }
+            }
+            ElementState::Released => {
+                self.buttons_held.remove(&button);
+            }
+        }
+    }
+}
 
 pub fn handle_keyboard_event(event: KeyEvent, app_state: &mut crate::state::State) {
     if let KeyEvent {
@@ -11,34 +95,75 @@ pub fn handle_keyboard_event(event: KeyEvent, app_state: &mut crate::state::Stat
         ..
     } = event
     {
-        move_camera(code, app_state, state)
+        app_state.input.key_input(code, state);
+        if state == ElementState::Pressed && !event.repeat {
+            match code {
+                SCREENSHOT_KEY => app_state.request_screenshot(screenshot_file_name()),
+                CAMERA_MODE_KEY => match app_state.camera.mode {
+                    CameraMode::Fly => {
+                        app_state.set_camera_controller(Box::new(OrbitController::default()))
+                    }
+                    CameraMode::LookAt => {
+                        app_state.set_camera_controller(Box::new(FlyController::default()))
+                    }
+                },
+                KeyCode::Escape if app_state.camera.mode == CameraMode::Fly => {
+                    app_state.set_camera_controller(Box::new(OrbitController::default()))
+                }
+                FRAME_KEY => app_state.frame_selected(),
+                _ => {}
+            }
+        }
+        app_state
+            .controller
+            .keyboard_input(&mut app_state.camera, code, state);
     };
 }
 
-fn move_camera(code: KeyCode, app_state: &mut crate::state::State, state: ElementState) {
-    if state != ElementState::Pressed {
-        return;
-    };
-
-    const SPEED: f32 = 0.2;
-    let mut movement = Vec3::new(0.0, 0.0, 0.0);
-
-    let camera = &app_state.camera;
-    let forward = -camera.position;
-
-    let forward_norm = forward.normalize();
+pub fn handle_mouse_input(
+    button: MouseButton,
+    state: ElementState,
+    app_state: &mut crate::state::State,
+) {
+    app_state.input.mouse_input(button, state);
+    app_state
+        .controller
+        .mouse_input(&mut app_state.camera, button, state);
+}
 
-    let right = forward_norm.cross(glam::Vec3::new(0.0, 1.0, 0.0));
-    match code {
-        KeyCode::KeyW => movement += forward_norm * SPEED,
-        KeyCode::KeyA => movement -= right.normalize() * SPEED,
-        KeyCode::KeyS => movement -= forward_norm * SPEED,
-        KeyCode::KeyD => movement += right.normalize() * SPEED,
-        KeyCode::KeyE => movement.y += SPEED,
-        KeyCode::KeyQ => movement.y -= SPEED,
+pub fn handle_mouse_motion(delta: (f64, f64), app_state: &mut crate::state::State) {
+    app_state.input.mouse_delta += glam::Vec2::new(delta.0 as f32, delta.1 as f32);
+    app_state
+        .controller
+        .mouse_motion(&mut app_state.camera, delta);
+}
 
-        _ => {}
+pub fn handle_mouse_wheel(delta: MouseScrollDelta, app_state: &mut crate::state::State) {
+    let lines = match delta {
+        MouseScrollDelta::LineDelta(_, y) => y,
+        MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_LINE,
     };
+    app_state.input.scroll_lines += lines;
+    app_state
+        .controller
+        .mouse_wheel(&mut app_state.camera, lines);
+}
+
+pub(crate) fn grab_cursor(window: &winit::window::Window, grab: bool) {
+    if grab {
+        let _ = window
+            .set_cursor_grab(CursorGrabMode::Locked)
+            .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined));
+    } else {
+        let _ = window.set_cursor_grab(CursorGrabMode::None);
+    }
+    window.set_cursor_visible(!grab);
+}
 
-    app_state.camera.apply_movement(movement);
+fn screenshot_file_name() -> String {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    format!("screenshot-{}.png", timestamp)
 }
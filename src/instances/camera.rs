@@ -1,17 +1,35 @@
 use glam::Vec3;
 
+const MAX_PITCH: f32 = 89.0;
+const MIN_DISTANCE: f32 = 0.01;
+
+fn direction(yaw: f32, pitch: f32) -> Vec3 {
+    let (yaw_sin, yaw_cos) = yaw.to_radians().sin_cos();
+    let (pitch_sin, pitch_cos) = pitch.to_radians().sin_cos();
+    Vec3::new(pitch_cos * yaw_cos, pitch_sin, pitch_cos * yaw_sin)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    LookAt,
+    Fly,
+}
+
 pub struct Camera {
     pub position: Vec3,
+    pub target: Vec3,
     pub yaw: f32,
     pub pitch: f32,
     pub fov: f32,
     pub aspect_ratio: f32,
+    pub mode: CameraMode,
 }
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct CameraUniform {
     pub view_proj: [[f32; 4]; 4],
+    pub view_position: [f32; 4],
 }
 
 impl Default for CameraUniform {
@@ -24,12 +42,14 @@ impl CameraUniform {
     pub fn new() -> Self {
         CameraUniform {
             view_proj: glam::Mat4::IDENTITY.to_cols_array_2d(),
+            view_position: [0.0; 4],
         }
     }
 
     pub fn update_view_proj(&mut self, camera: &Camera) {
         self.view_proj =
             (camera.get_projection_matrix() * camera.get_view_matrix()).to_cols_array_2d();
+        self.view_position = camera.position.extend(1.0).to_array();
     }
 }
 
@@ -38,29 +58,125 @@ impl Camera {
         self.position += movement;
     }
 
-    pub fn new(window: &winit::window::Window) -> Self {
-        let size = window.inner_size();
-
+    pub fn new(width: u32, height: u32) -> Self {
         Camera {
             position: Vec3::new(6.0, 2.0, -6.0),
+            target: Vec3::ZERO,
             yaw: 0.0,
             pitch: 0.0,
             fov: 45.5,
-            aspect_ratio: size.width as f32 / size.height as f32,
+            aspect_ratio: width.max(1) as f32 / height.max(1) as f32,
+            mode: CameraMode::LookAt,
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        match (self.mode, mode) {
+            // Start flying in the direction the camera was already looking.
+            (CameraMode::LookAt, CameraMode::Fly) => self.face_target(),
+            // Keep looking at the same point when switching back.
+            (CameraMode::Fly, CameraMode::LookAt) => {
+                self.target = self.position + self.forward() * self.distance();
+            }
+            _ => {}
+        }
+        self.mode = mode;
+    }
+
+    // Enters `mode` from the current position and target, whatever the previous mode was.
+    pub fn reset_mode(&mut self, mode: CameraMode) {
+        if mode == CameraMode::Fly {
+            self.face_target();
+        }
+        self.mode = mode;
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.set_mode(match self.mode {
+            CameraMode::LookAt => CameraMode::Fly,
+            CameraMode::Fly => CameraMode::LookAt,
+        });
+    }
+
+    pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = (self.yaw + yaw) % 360.0;
+        self.pitch = (self.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        match self.mode {
+            CameraMode::LookAt => (self.target - self.position).normalize_or_zero(),
+            CameraMode::Fly => direction(self.yaw, self.pitch),
         }
     }
 
-    pub fn reconfigure_aspect_ratio(&mut self, window: &winit::window::Window) {
-        let size = window.inner_size();
-        self.aspect_ratio = size.width as f32 / size.height as f32;
+    pub fn right(&self) -> Vec3 {
+        self.forward()
+            .cross(Vec3::new(0.0, 1.0, 0.0))
+            .normalize_or_zero()
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.position.distance(self.target)
+    }
+
+    pub fn orbit(&mut self, yaw: f32, pitch: f32) {
+        let distance = self.distance();
+        self.face_target();
+        self.rotate(yaw, pitch);
+        self.position = self.target - direction(self.yaw, self.pitch) * distance;
+    }
+
+    pub fn pan(&mut self, right: f32, up: f32) {
+        let right_dir = self.right();
+        let up_dir = right_dir.cross(self.forward());
+        self.translate((right_dir * right + up_dir * up) * self.distance());
+    }
+
+    // Moves the camera together with its target.
+    pub fn translate(&mut self, offset: Vec3) {
+        self.position += offset;
+        self.target += offset;
+    }
+
+    pub fn dolly(&mut self, factor: f32) {
+        let distance = (self.distance() * factor).max(MIN_DISTANCE);
+        self.position = self.target - (self.target - self.position).normalize_or_zero() * distance;
+    }
+
+    pub fn frame(&mut self, center: Vec3, radius: f32) {
+        let forward = self.forward();
+        let fov = self
+            .fov
+            .to_radians()
+            .min(self.fov.to_radians() * self.aspect_ratio);
+        let distance = (radius / (fov / 2.0).sin()).max(MIN_DISTANCE);
+        self.target = center;
+        self.position = center - forward * distance;
+    }
+
+    fn face_target(&mut self) {
+        let forward = (self.target - self.position).normalize_or_zero();
+        self.yaw = forward.z.atan2(forward.x).to_degrees();
+        self.pitch = forward.y.asin().to_degrees().clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    pub fn reconfigure_aspect_ratio(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.aspect_ratio = width as f32 / height as f32;
     }
 
     pub fn get_view_matrix(&self) -> glam::Mat4 {
-        glam::Mat4::look_at_rh(
-            self.position,
-            Vec3::new(0.0, 0.0, 0.0),
-            Vec3::new(0.0, 1.0, 0.0),
-        )
+        match self.mode {
+            CameraMode::LookAt => {
+                glam::Mat4::look_at_rh(self.position, self.target, Vec3::new(0.0, 1.0, 0.0))
+            }
+            CameraMode::Fly => {
+                glam::Mat4::look_to_rh(self.position, self.forward(), Vec3::new(0.0, 1.0, 0.0))
+            }
+        }
     }
 
     pub fn get_projection_matrix(&self) -> glam::Mat4 {
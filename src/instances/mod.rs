@@ -1,14 +1,422 @@
 pub mod camera;
+mod scene;
+
+use std::sync::Arc;
+
+use glam::{Mat4, Quat, Vec3};
+
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Transform {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn to_matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
 
 #[derive(Debug)]
 pub struct Instance {
-    pub model: crate::models::Model,
+    pub model: Option<Arc<crate::models::Model>>,
+    transform: Transform,
+    name: Option<String>,
+    visible: bool,
+    casts_shadows: bool,
+    parent: Option<InstanceId>,
+    children: Vec<InstanceId>,
+    world_matrix: Mat4,
+    visible_in_tree: bool,
 }
 
 impl Instance {
-    pub fn new(file_path: &str, state: &crate::state::State, position: glam::Vec3) -> Self {
+    pub fn new(model: Arc<crate::models::Model>, transform: Transform) -> Self {
+        Self {
+            model: Some(model),
+            ..Self::empty(transform)
+        }
+    }
+
+    pub fn empty(transform: Transform) -> Self {
         Self {
-            model: crate::models::load_model(file_path, state, position),
+            model: None,
+            transform,
+            name: None,
+            visible: true,
+            casts_shadows: true,
+            parent: None,
+            children: Vec::new(),
+            world_matrix: transform.to_matrix(),
+            visible_in_tree: true,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.name = Some(name.into());
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    pub fn set_casts_shadows(&mut self, casts_shadows: bool) {
+        self.casts_shadows = casts_shadows;
+    }
+
+    // Hidden ancestors hide the whole subtree, as of the last transform update.
+    pub fn is_visible_in_tree(&self) -> bool {
+        self.visible_in_tree
+    }
+
+    pub fn parent(&self) -> Option<InstanceId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[InstanceId] {
+        &self.children
+    }
+
+    pub fn world_matrix(&self) -> Mat4 {
+        self.world_matrix
+    }
+
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    pub fn translation(&self) -> Vec3 {
+        self.transform.translation
+    }
+
+    pub fn set_translation(&mut self, translation: Vec3) {
+        self.transform.translation = translation;
+    }
+
+    pub fn rotation(&self) -> Quat {
+        self.transform.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: Quat) {
+        self.transform.rotation = rotation;
+    }
+
+    pub fn scale(&self) -> Vec3 {
+        self.transform.scale
+    }
+
+    pub fn set_scale(&mut self, scale: Vec3) {
+        self.transform.scale = scale;
+    }
+
+    pub fn bounds(&self) -> crate::models::Bounds {
+        match &self.model {
+            Some(model) => model.bounds.transformed(self.world_matrix),
+            None => crate::models::Bounds::EMPTY,
+        }
+    }
+
+    pub fn to_raw(&self) -> InstanceRaw {
+        let model = self.world_matrix;
+        InstanceRaw {
+            model: model.to_cols_array_2d(),
+            normal: glam::Mat3::from_mat4(model)
+                .inverse()
+                .transpose()
+                .to_cols_array_2d(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InstanceId {
+    index: u32,
+    generation: u32,
+}
+
+#[derive(Debug)]
+struct Slot {
+    generation: u32,
+    instance: Option<Instance>,
+}
+
+// Slots are reused after removal, so ids carry a generation to stay unique.
+#[derive(Debug, Default)]
+pub struct Instances {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl Instances {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, mut instance: Instance) -> InstanceId {
+        instance.parent = None;
+        instance.children.clear();
+        self.len += 1;
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.instance = Some(instance);
+                InstanceId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    instance: Some(instance),
+                });
+                InstanceId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    // Children are removed along with their parent.
+    pub fn remove(&mut self, id: InstanceId) -> Option<Instance> {
+        self.detach(id);
+        let mut instance = self.take(id)?;
+        let mut pending = std::mem::take(&mut instance.children);
+        while let Some(child) = pending.pop() {
+            if let Some(child) = self.take(child) {
+                pending.extend(child.children);
+            }
+        }
+        Some(instance)
+    }
+
+    fn take(&mut self, id: InstanceId) -> Option<Instance> {
+        let slot = self.slots.get_mut(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
         }
+        let instance = slot.instance.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(id.index);
+        self.len -= 1;
+        Some(instance)
+    }
+
+    pub fn get(&self, id: InstanceId) -> Option<&Instance> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)?
+            .instance
+            .as_ref()
+    }
+
+    pub fn get_mut(&mut self, id: InstanceId) -> Option<&mut Instance> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)?
+            .instance
+            .as_mut()
+    }
+
+    pub fn contains(&self, id: InstanceId) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn find(&self, name: &str) -> Option<InstanceId> {
+        self.iter()
+            .find(|(_, instance)| instance.name() == Some(name))
+            .map(|(id, _)| id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        for index in 0..self.slots.len() {
+            let slot = &mut self.slots[index];
+            if slot.instance.take().is_some() {
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free.push(index as u32);
+            }
+        }
+        self.len = 0;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (InstanceId, &Instance)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let id = InstanceId {
+                index: index as u32,
+                generation: slot.generation,
+            };
+            slot.instance.as_ref().map(|instance| (id, instance))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (InstanceId, &mut Instance)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let id = InstanceId {
+                    index: index as u32,
+                    generation: slot.generation,
+                };
+                slot.instance.as_mut().map(|instance| (id, instance))
+            })
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceRaw {
+    pub model: [[f32; 4]; 4],
+    pub normal: [[f32; 3]; 3],
+}
+
+impl InstanceRaw {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 16]>() as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 19]>() as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 22]>() as wgpu::BufferAddress,
+                    shader_location: 11,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(name: &str) -> Instance {
+        let mut instance = Instance::empty(Transform::IDENTITY);
+        instance.set_name(name);
+        instance
+    }
+
+    #[test]
+    fn reuses_removed_slots_with_a_new_generation() {
+        let mut instances = Instances::new();
+        let first = instances.insert(named("first"));
+        assert_eq!(instances.remove(first).unwrap().name(), Some("first"));
+
+        let second = instances.insert(named("second"));
+        assert_eq!(second.index, first.index);
+        assert_ne!(second.generation, first.generation);
+        assert_eq!(instances.get(second).unwrap().name(), Some("second"));
+    }
+
+    #[test]
+    fn ignores_stale_ids() {
+        let mut instances = Instances::new();
+        let stale = instances.insert(named("first"));
+        instances.remove(stale);
+        let current = instances.insert(named("second"));
+
+        assert!(instances.get(stale).is_none());
+        assert!(instances.get_mut(stale).is_none());
+        assert!(instances.remove(stale).is_none());
+        assert!(instances.contains(current));
+        assert_eq!(instances.len(), 1);
+    }
+
+    #[test]
+    fn tracks_len_and_iter_after_removals() {
+        let mut instances = Instances::new();
+        let ids = ["a", "b", "c", "d"].map(|name| instances.insert(named(name)));
+        instances.remove(ids[1]);
+        instances.remove(ids[3]);
+
+        assert_eq!(instances.len(), 2);
+        let names = instances
+            .iter()
+            .map(|(_, instance)| instance.name().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a", "c"]);
+        assert_eq!(instances.find("c"), Some(ids[2]));
+        assert_eq!(instances.find("b"), None);
+
+        instances.clear();
+        assert!(instances.is_empty());
+        assert_eq!(instances.iter().count(), 0);
     }
 }
@@ -0,0 +1,170 @@
+use glam::Mat4;
+
+use super::{InstanceId, Instances};
+
+impl Instances {
+    pub fn parent(&self, id: InstanceId) -> Option<InstanceId> {
+        self.get(id)?.parent
+    }
+
+    pub fn children(&self, id: InstanceId) -> &[InstanceId] {
+        self.get(id)
+            .map(|instance| instance.children.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn roots(&self) -> impl Iterator<Item = InstanceId> + '_ {
+        self.iter()
+            .filter(|(_, instance)| instance.parent.is_none())
+            .map(|(id, _)| id)
+    }
+
+    // Depth-first, parents before their children, not including `id` itself.
+    pub fn descendants(&self, id: InstanceId) -> Vec<InstanceId> {
+        let mut descendants = Vec::new();
+        let mut pending = self.children(id).iter().rev().copied().collect::<Vec<_>>();
+        while let Some(child) = pending.pop() {
+            descendants.push(child);
+            pending.extend(self.children(child).iter().rev());
+        }
+        descendants
+    }
+
+    pub fn ancestors(&self, id: InstanceId) -> impl Iterator<Item = InstanceId> + '_ {
+        std::iter::successors(self.parent(id), |&id| self.parent(id))
+    }
+
+    // The local transform is kept, so the child moves with its new parent.
+    // Returns false if either id is stale or the change would create a cycle.
+    pub fn set_parent(&mut self, child: InstanceId, parent: Option<InstanceId>) -> bool {
+        if !self.contains(child) {
+            return false;
+        }
+        if let Some(parent) = parent {
+            if !self.contains(parent)
+                || parent == child
+                || self.ancestors(parent).any(|ancestor| ancestor == child)
+            {
+                return false;
+            }
+        }
+
+        self.detach(child);
+        if let Some(parent) = parent {
+            self.get_mut(parent)
+                .expect("parent was checked above")
+                .children
+                .push(child);
+        }
+        self.get_mut(child).expect("child was checked above").parent = parent;
+        true
+    }
+
+    pub(super) fn detach(&mut self, id: InstanceId) {
+        let Some(parent) = self.parent(id) else {
+            return;
+        };
+        if let Some(parent) = self.get_mut(parent) {
+            parent.children.retain(|&child| child != id);
+        }
+        if let Some(instance) = self.get_mut(id) {
+            instance.parent = None;
+        }
+    }
+
+    pub fn update_world_transforms(&mut self) {
+        let mut pending = self
+            .roots()
+            .map(|id| (id, Mat4::IDENTITY, true))
+            .collect::<Vec<_>>();
+        while let Some((id, parent_matrix, parent_visible)) = pending.pop() {
+            let Some(instance) = self.get_mut(id) else {
+                continue;
+            };
+            instance.world_matrix = parent_matrix * instance.transform.to_matrix();
+            instance.visible_in_tree = parent_visible && instance.visible;
+
+            let (matrix, visible) = (instance.world_matrix, instance.visible_in_tree);
+            pending.extend(
+                instance
+                    .children
+                    .iter()
+                    .map(|&child| (child, matrix, visible)),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+    use crate::instances::{Instance, Transform};
+
+    fn at(instances: &mut Instances, x: f32) -> InstanceId {
+        instances.insert(Instance::empty(Transform::from_translation(Vec3::X * x)))
+    }
+
+    #[test]
+    fn rejects_parenting_onto_a_descendant() {
+        let mut instances = Instances::new();
+        let root = at(&mut instances, 0.0);
+        let child = at(&mut instances, 0.0);
+        let grandchild = at(&mut instances, 0.0);
+        assert!(instances.set_parent(child, Some(root)));
+        assert!(instances.set_parent(grandchild, Some(child)));
+
+        assert!(!instances.set_parent(root, Some(grandchild)));
+        assert!(!instances.set_parent(root, Some(root)));
+        assert_eq!(instances.parent(root), None);
+        assert_eq!(instances.descendants(root), [child, grandchild]);
+    }
+
+    #[test]
+    fn composes_world_transforms_through_the_tree() {
+        let mut instances = Instances::new();
+        let root = at(&mut instances, 1.0);
+        let child = at(&mut instances, 2.0);
+        let grandchild = at(&mut instances, 4.0);
+        instances.set_parent(child, Some(root));
+        instances.set_parent(grandchild, Some(child));
+        instances.get_mut(root).unwrap().set_scale(Vec3::splat(2.0));
+        instances.get_mut(child).unwrap().set_visible(false);
+        instances.update_world_transforms();
+
+        let origin = |id| {
+            instances
+                .get(id)
+                .unwrap()
+                .world_matrix()
+                .transform_point3(Vec3::ZERO)
+        };
+        assert_eq!(origin(root), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(origin(child), Vec3::new(5.0, 0.0, 0.0));
+        assert_eq!(origin(grandchild), Vec3::new(13.0, 0.0, 0.0));
+
+        let visible = |id| instances.get(id).unwrap().is_visible_in_tree();
+        assert!(visible(root));
+        assert!(!visible(child));
+        assert!(!visible(grandchild));
+    }
+
+    #[test]
+    fn removes_subtrees_with_their_parent() {
+        let mut instances = Instances::new();
+        let root = at(&mut instances, 0.0);
+        let child = at(&mut instances, 0.0);
+        let grandchild = at(&mut instances, 0.0);
+        let sibling = at(&mut instances, 0.0);
+        instances.set_parent(child, Some(root));
+        instances.set_parent(grandchild, Some(child));
+        instances.set_parent(sibling, Some(root));
+
+        instances.remove(child);
+        assert!(!instances.contains(child));
+        assert!(!instances.contains(grandchild));
+        assert_eq!(instances.children(root), [sibling]);
+        assert_eq!(instances.len(), 2);
+    }
+}
@@ -1,15 +1,24 @@
 mod app;
+pub mod assets;
+pub mod config;
+pub mod controllers;
+mod error;
 pub mod input;
 pub mod instances;
+pub mod lights;
 pub mod models;
 mod renderer;
+pub mod resources;
+pub mod scene;
+pub mod shadows;
 pub mod state;
 pub mod texture;
 
+pub use app::{AppBuilder, MiyeApp};
+pub use error::{Error, Result};
 pub use glam;
+pub use renderer::DrawModel;
 
-pub fn run(f: fn(&mut state::State) -> ()) {
-    let event_loop = winit::event_loop::EventLoop::new().unwrap();
-    let mut app = app::App::Uninitialized(f);
-    event_loop.run_app(&mut app).unwrap();
+pub fn run<A: MiyeApp>(app: A) -> Result<()> {
+    AppBuilder::new().run(app)
 }
@@ -0,0 +1,158 @@
+use glam::Vec3;
+
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 4;
+pub const MAX_POINT_LIGHTS: usize = 8;
+
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DirectionalLight {
+    pub direction: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, color: Vec3, intensity: f32) -> Self {
+        DirectionalLight {
+            direction,
+            color,
+            intensity,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PointLight {
+    pub position: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+    pub range: f32,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, color: Vec3, intensity: f32, range: f32) -> Self {
+        PointLight {
+            position,
+            color,
+            intensity,
+            range,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Lights {
+    pub ambient: Vec3,
+    // Scales the environment image that lights PBR materials in place of `ambient`.
+    pub environment_intensity: f32,
+    pub directional: Vec<DirectionalLight>,
+    pub point: Vec<PointLight>,
+}
+
+impl Default for Lights {
+    fn default() -> Self {
+        Lights {
+            ambient: Vec3::splat(0.1),
+            environment_intensity: 1.0,
+            directional: vec![DirectionalLight::new(
+                Vec3::new(-0.5, -1.0, -0.3),
+                Vec3::ONE,
+                1.0,
+            )],
+            point: Vec::new(),
+        }
+    }
+}
+
+impl Lights {
+    pub fn add_directional(&mut self, light: DirectionalLight) {
+        self.directional.push(light);
+    }
+
+    pub fn add_point(&mut self, light: PointLight) {
+        self.point.push(light);
+    }
+
+    pub fn clear(&mut self) {
+        self.directional.clear();
+        self.point.clear();
+    }
+
+    pub fn to_uniform(&self) -> LightsUniform {
+        let mut uniform = LightsUniform {
+            ambient: self.ambient.to_array(),
+            directional_count: self.directional.len().min(MAX_DIRECTIONAL_LIGHTS) as u32,
+            point_count: self.point.len().min(MAX_POINT_LIGHTS) as u32,
+            environment_intensity: self.environment_intensity,
+            _padding: [0; 2],
+            directional: [DirectionalLightRaw::default(); MAX_DIRECTIONAL_LIGHTS],
+            point: [PointLightRaw::default(); MAX_POINT_LIGHTS],
+        };
+
+        for (raw, light) in uniform.directional.iter_mut().zip(&self.directional) {
+            *raw = DirectionalLightRaw {
+                direction: light.direction.normalize_or_zero().to_array(),
+                _padding: 0.0,
+                color: light.color.to_array(),
+                intensity: light.intensity,
+            };
+        }
+        for (raw, light) in uniform.point.iter_mut().zip(&self.point) {
+            *raw = PointLightRaw {
+                position: light.position.to_array(),
+                range: light.range,
+                color: light.color.to_array(),
+                intensity: light.intensity,
+            };
+        }
+
+        uniform
+    }
+}
+
+// A bright sky fading to a darker ground, used until an environment image is set.
+pub(crate) fn default_environment() -> image::RgbaImage {
+    let sky = Vec3::new(0.75, 0.85, 1.0);
+    let horizon = Vec3::splat(0.9);
+    let ground = Vec3::new(0.3, 0.28, 0.25);
+    image::RgbaImage::from_fn(256, 128, |_, y| {
+        let t = (y as f32 + 0.5) / 128.0;
+        let color = if t < 0.5 {
+            sky.lerp(horizon, t * 2.0)
+        } else {
+            horizon.lerp(ground, ((t - 0.5) * 4.0).min(1.0))
+        };
+        let [r, g, b] = (color * 255.0).round().to_array();
+        image::Rgba([r as u8, g as u8, b as u8, 255])
+    })
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DirectionalLightRaw {
+    direction: [f32; 3],
+    _padding: f32,
+    color: [f32; 3],
+    intensity: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PointLightRaw {
+    position: [f32; 3],
+    range: f32,
+    color: [f32; 3],
+    intensity: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightsUniform {
+    ambient: [f32; 3],
+    directional_count: u32,
+    point_count: u32,
+    environment_intensity: f32,
+    _padding: [u32; 2],
+    directional: [DirectionalLightRaw; MAX_DIRECTIONAL_LIGHTS],
+    point: [PointLightRaw; MAX_POINT_LIGHTS],
+}
@@ -0,0 +1,478 @@
+use std::path::Path;
+
+use glam::{Mat3, Mat4, Vec3, Vec4};
+
+use super::{
+    compute_normals, read_resource, Bounds, Material, Mesh, Model, PbrFactors, PbrTextures, Vertex,
+};
+use crate::error::{Error, Result};
+use crate::resources::{FileResolver, ResourceResolver};
+use crate::texture::{MipmapGenerator, SamplerOptions, Texture};
+
+pub fn load_gltf(file_path: &str, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Model> {
+    let resolver = FileResolver::default();
+    let bytes = read_resource(&resolver, Path::new(file_path))?;
+    load_gltf_from_bytes(file_path, &bytes, &resolver, device, queue)
+}
+
+pub fn load_gltf_from_bytes(
+    file_path: &str,
+    bytes: &[u8],
+    resolver: &dyn ResourceResolver,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> Result<Model> {
+    let (document, buffers, images) = import(file_path, bytes, resolver)?;
+
+    let mipmaps = MipmapGenerator::new();
+    let mut materials = document
+        .materials()
+        .map(|material| load_material(device, queue, &mipmaps, &material, &images))
+        .collect::<Vec<_>>();
+    let default_material = materials.len();
+    materials.push(Material::default_material(device, queue));
+
+    let mut builder = ModelBuilder {
+        device,
+        label: file_path,
+        buffers: &buffers,
+        default_material,
+        meshes: Vec::new(),
+        bounds: Bounds::EMPTY,
+    };
+
+    match document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        Some(scene) => {
+            for node in scene.nodes() {
+                builder.add_node(&node, Mat4::IDENTITY)?;
+            }
+        }
+        None => {
+            for mesh in document.meshes() {
+                builder.add_mesh(&mesh, Mat4::IDENTITY)?;
+            }
+        }
+    }
+
+    Ok(Model {
+        mesh: builder.meshes,
+        material: materials,
+        bounds: builder.bounds,
+    })
+}
+
+struct ModelBuilder<'a> {
+    device: &'a wgpu::Device,
+    label: &'a str,
+    buffers: &'a [gltf::buffer::Data],
+    default_material: usize,
+    meshes: Vec<Mesh>,
+    bounds: Bounds,
+}
+
+impl ModelBuilder<'_> {
+    fn add_node(&mut self, node: &gltf::Node, parent: Mat4) -> Result<()> {
+        let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+        if let Some(mesh) = node.mesh() {
+            self.add_mesh(&mesh, transform)?;
+        }
+        for child in node.children() {
+            self.add_node(&child, transform)?;
+        }
+        Ok(())
+    }
+
+    fn add_mesh(&mut self, mesh: &gltf::Mesh, transform: Mat4) -> Result<()> {
+        let normal_matrix = Mat3::from_mat4(transform).inverse().transpose();
+        let name = mesh.name().unwrap_or(self.label);
+
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                continue;
+            }
+
+            let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+            let Some(positions) = reader.read_positions() else {
+                continue;
+            };
+            let positions = positions
+                .map(|p| transform.transform_point3(Vec3::from(p)))
+                .collect::<Vec<_>>();
+
+            let indices = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect::<Vec<_>>(),
+                None => (0..positions.len() as u32).collect(),
+            };
+            if indices.iter().any(|&i| i as usize >= positions.len()) {
+                return Err(Error::InvalidModel {
+                    path: self.label.into(),
+                    message: "primitive references a missing vertex".to_string(),
+                });
+            }
+
+            let normals = match reader.read_normals() {
+                Some(normals) => normals
+                    .map(|n| (normal_matrix * Vec3::from(n)).normalize_or_zero())
+                    .collect::<Vec<_>>(),
+                None => {
+                    let flat = positions
+                        .iter()
+                        .flat_map(|p| p.to_array())
+                        .collect::<Vec<_>>();
+                    compute_normals(&flat, &indices)
+                        .chunks_exact(3)
+                        .map(Vec3::from_slice)
+                        .collect()
+                }
+            };
+
+            let tex_coords = reader
+                .read_tex_coords(0)
+                .map(|tex_coords| tex_coords.into_f32().collect::<Vec<_>>())
+                .unwrap_or_default();
+
+            let vertices = positions
+                .iter()
+                .enumerate()
+                .map(|(i, position)| {
+                    Vertex::new(
+                        position.to_array(),
+                        tex_coords.get(i).copied().unwrap_or_default(),
+                        normals.get(i).copied().unwrap_or(Vec3::Y).to_array(),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            self.bounds = self
+                .bounds
+                .union(&Bounds::from_points(positions.iter().copied()));
+            self.meshes.push(Mesh::new(
+                self.device,
+                name,
+                &vertices,
+                &indices,
+                primitive
+                    .material()
+                    .index()
+                    .unwrap_or(self.default_material),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn load_material(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    mipmaps: &MipmapGenerator,
+    material: &gltf::Material,
+    images: &[image::RgbaImage],
+) -> Material {
+    let texture = |texture: gltf::Texture, srgb: bool| {
+        let data = images.get(texture.source().index())?;
+        let image = image::DynamicImage::ImageRgba8(data.clone());
+        let sampler = sampler_options(&texture.sampler());
+        let format = if srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+        Some(Texture::from_image_with_format(
+            device, queue, mipmaps, &image, "Texture", &sampler, format,
+        ))
+    };
+
+    let pbr = material.pbr_metallic_roughness();
+    let textures = PbrTextures {
+        base_color: pbr
+            .base_color_texture()
+            .and_then(|info| texture(info.texture(), true)),
+        metallic_roughness: pbr
+            .metallic_roughness_texture()
+            .and_then(|info| texture(info.texture(), false)),
+        normal: material
+            .normal_texture()
+            .and_then(|info| texture(info.texture(), false)),
+        occlusion: material
+            .occlusion_texture()
+            .and_then(|info| texture(info.texture(), false)),
+        emissive: material
+            .emissive_texture()
+            .and_then(|info| texture(info.texture(), true)),
+    };
+
+    let factors = PbrFactors {
+        base_color: Vec4::from(pbr.base_color_factor()),
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+        emissive: Vec3::from(material.emissive_factor()),
+        normal_scale: material.normal_texture().map_or(1.0, |info| info.scale()),
+        occlusion_strength: material
+            .occlusion_texture()
+            .map_or(1.0, |info| info.strength()),
+    };
+
+    Material::pbr(
+        device,
+        queue,
+        material.name().unwrap_or("Material").to_string(),
+        factors,
+        textures,
+    )
+}
+
+fn sampler_options(sampler: &gltf::texture::Sampler) -> SamplerOptions {
+    use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+    use wgpu::{AddressMode, FilterMode};
+
+    let address_mode = |mode| match mode {
+        WrappingMode::ClampToEdge => AddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => AddressMode::MirrorRepeat,
+        WrappingMode::Repeat => AddressMode::Repeat,
+    };
+    let mag_filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => FilterMode::Nearest,
+        _ => FilterMode::Linear,
+    };
+    let (min_filter, mipmap_filter) = match sampler.min_filter() {
+        Some(MinFilter::Nearest | MinFilter::NearestMipmapNearest) => {
+            (FilterMode::Nearest, FilterMode::Nearest)
+        }
+        Some(MinFilter::NearestMipmapLinear) => (FilterMode::Nearest, FilterMode::Linear),
+        Some(MinFilter::Linear | MinFilter::LinearMipmapNearest) => {
+            (FilterMode::Linear, FilterMode::Nearest)
+        }
+        Some(MinFilter::LinearMipmapLinear) | None => (FilterMode::Linear, FilterMode::Linear),
+    };
+
+    SamplerOptions {
+        address_mode_u: address_mode(sampler.wrap_s()),
+        address_mode_v: address_mode(sampler.wrap_t()),
+        mag_filter,
+        min_filter,
+        mipmap_filter,
+        ..Default::default()
+    }
+}
+
+// Mirrors gltf::import, but external buffers and images go through the resolver.
+fn import(
+    file_path: &str,
+    bytes: &[u8],
+    resolver: &dyn ResourceResolver,
+) -> Result<(
+    gltf::Document,
+    Vec<gltf::buffer::Data>,
+    Vec<image::RgbaImage>,
+)> {
+    let gltf_error = |source| Error::Gltf {
+        path: file_path.into(),
+        source,
+    };
+    let base = Path::new(file_path).parent().unwrap_or(Path::new(""));
+    let read_external = |uri: &str| read_resource(resolver, &base.join(decode_uri(uri)));
+
+    let gltf::Gltf { document, mut blob } = gltf::Gltf::from_slice(bytes).map_err(gltf_error)?;
+
+    let mut buffers = Vec::new();
+    for buffer in document.buffers() {
+        let data = match buffer.source() {
+            gltf::buffer::Source::Uri(uri) if !uri.starts_with("data:") => {
+                let mut data = read_external(uri)?;
+                while data.len() % 4 != 0 {
+                    data.push(0);
+                }
+                gltf::buffer::Data(data)
+            }
+            source => gltf::buffer::Data::from_source_and_blob(source, None, &mut blob)
+                .map_err(gltf_error)?,
+        };
+        if data.len() < buffer.length() {
+            return Err(gltf_error(gltf::Error::BufferLength {
+                buffer: buffer.index(),
+                expected: buffer.length(),
+                actual: data.len(),
+            }));
+        }
+        buffers.push(data);
+    }
+
+    let mut images = Vec::new();
+    for image in document.images() {
+        let image = match image.source() {
+            gltf::image::Source::Uri { uri, .. } if !uri.starts_with("data:") => {
+                image::load_from_memory(&read_external(uri)?)?.to_rgba8()
+            }
+            // Embedded images never touch the base path, it only has to be present.
+            source => to_rgba8(
+                &gltf::image::Data::from_source(source, Some(Path::new("")), &buffers)
+                    .map_err(gltf_error)?,
+            ),
+        };
+        images.push(image);
+    }
+
+    Ok((document, buffers, images))
+}
+
+fn decode_uri(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn to_rgba8(data: &gltf::image::Data) -> image::RgbaImage {
+    use gltf::image::Format;
+
+    let (channels, bytes_per_channel) = match data.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+
+    let channel = |bytes: &[u8]| -> u8 {
+        match bytes_per_channel {
+            1 => bytes[0],
+            2 => (u16::from_le_bytes([bytes[0], bytes[1]]) >> 8) as u8,
+            _ => {
+                let value = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                (value.clamp(0.0, 1.0) * 255.0).round() as u8
+            }
+        }
+    };
+
+    let pixels = data
+        .pixels
+        .chunks_exact(channels * bytes_per_channel)
+        .flat_map(|pixel| {
+            let c = pixel
+                .chunks_exact(bytes_per_channel)
+                .map(channel)
+                .collect::<Vec<_>>();
+            match c.len() {
+                1 => [c[0], c[0], c[0], 255],
+                2 => [c[0], c[0], c[0], c[1]],
+                3 => [c[0], c[1], c[2], 255],
+                _ => [c[0], c[1], c[2], c[3]],
+            }
+        })
+        .collect();
+
+    image::RgbaImage::from_raw(data.width, data.height, pixels).expect("image size mismatch")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::MemoryResolver;
+
+    // One triangle without normals, whose last index is given by the test.
+    fn triangle(last_index: u16) -> (String, Vec<u8>) {
+        let mut buffer = Vec::new();
+        for value in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            buffer.extend(value.to_le_bytes());
+        }
+        for index in [0u16, 1, last_index, 0] {
+            buffer.extend(index.to_le_bytes());
+        }
+        let json = format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}}, "indices": 1}}]}}],
+                "buffers": [{{"byteLength": {}, "uri": "triangle.bin"}}],
+                "bufferViews": [
+                    {{"buffer": 0, "byteOffset": 0, "byteLength": 36}},
+                    {{"buffer": 0, "byteOffset": 36, "byteLength": 6}}
+                ],
+                "accessors": [
+                    {{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                      "min": [0, 0, 0], "max": [1, 1, 0]}},
+                    {{"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}}
+                ]
+            }}"#,
+            buffer.len()
+        );
+        (json, buffer)
+    }
+
+    fn load(last_index: u16) -> Option<Result<Model>> {
+        let state = crate::state::headless_test_state()?;
+        let (json, buffer) = triangle(last_index);
+        let resolver = MemoryResolver::new().with("triangle.bin", buffer);
+        Some(load_gltf_from_bytes(
+            "triangle.gltf",
+            json.as_bytes(),
+            &resolver,
+            &state.device,
+            &state.queue,
+        ))
+    }
+
+    #[test]
+    fn computes_missing_normals() {
+        let Some(model) = load(2) else {
+            return;
+        };
+        assert_eq!(model.unwrap().mesh.len(), 1);
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        let Some(model) = load(3) else {
+            return;
+        };
+        assert!(matches!(model, Err(Error::InvalidModel { .. })));
+    }
+
+    #[test]
+    fn decodes_percent_escapes() {
+        assert_eq!(decode_uri("my%20texture.png"), "my texture.png");
+        assert_eq!(decode_uri("a%2Fb%2fc.png"), "a/b/c.png");
+        assert_eq!(decode_uri("plain.png"), "plain.png");
+    }
+
+    #[test]
+    fn decodes_multi_byte_utf8() {
+        assert_eq!(decode_uri("caf%C3%A9.png"), "caf\u{e9}.png");
+        assert_eq!(decode_uri("%E6%9C%A8.png"), "\u{6728}.png");
+        // Unescaped non-ASCII characters pass through untouched.
+        assert_eq!(decode_uri("\u{6728}%20a.png"), "\u{6728} a.png");
+    }
+
+    #[test]
+    fn keeps_invalid_escapes() {
+        assert_eq!(decode_uri("100%zz.png"), "100%zz.png");
+        assert_eq!(decode_uri("50%"), "50%");
+        assert_eq!(decode_uri("%2"), "%2");
+        assert_eq!(decode_uri("%%41"), "%A");
+        // Escapes that do not form UTF-8 are replaced instead of failing the whole path.
+        assert_eq!(decode_uri("%FF.png"), "\u{fffd}.png");
+    }
+}
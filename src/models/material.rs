@@ -0,0 +1,369 @@
+use wgpu::util::DeviceExt;
+
+use super::DEFAULT_SHININESS;
+use crate::texture::Texture;
+
+// Each kind is drawn with its own pipeline.
+#[derive(Debug)]
+pub enum Material {
+    Phong(Box<PhongMaterial>),
+    Pbr(Box<PbrMaterial>),
+}
+
+impl Material {
+    pub fn new(
+        device: &wgpu::Device,
+        name: String,
+        texture: Texture,
+        ambient: glam::Vec3,
+        diffuse: glam::Vec3,
+        specular: glam::Vec3,
+        shininess: f32,
+    ) -> Self {
+        Material::Phong(Box::new(PhongMaterial::new(
+            device, name, texture, ambient, diffuse, specular, shininess,
+        )))
+    }
+
+    pub fn pbr(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        name: String,
+        factors: PbrFactors,
+        textures: PbrTextures,
+    ) -> Self {
+        Material::Pbr(Box::new(PbrMaterial::new(
+            device, queue, name, factors, textures,
+        )))
+    }
+
+    pub fn default_material(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self::from_color(device, queue, "Default", glam::Vec3::splat(0.8))
+    }
+
+    pub fn from_color(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        name: &str,
+        color: glam::Vec3,
+    ) -> Self {
+        Self::new(
+            device,
+            name.to_string(),
+            Texture::white(device, queue),
+            color,
+            color,
+            glam::Vec3::ZERO,
+            DEFAULT_SHININESS,
+        )
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Material::Phong(material) => &material.name,
+            Material::Pbr(material) => &material.name,
+        }
+    }
+
+    pub fn is_pbr(&self) -> bool {
+        matches!(self, Material::Pbr(_))
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        match self {
+            Material::Phong(material) => &material.bind_group,
+            Material::Pbr(material) => &material.bind_group,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PhongMaterial {
+    pub name: String,
+    pub texture: Texture,
+    pub ambient: glam::Vec3,
+    pub diffuse: glam::Vec3,
+    pub specular: glam::Vec3,
+    pub shininess: f32,
+    pub uniform_buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+    ambient: [f32; 3],
+    _padding0: f32,
+    diffuse: [f32; 3],
+    _padding1: f32,
+    specular: [f32; 3],
+    shininess: f32,
+}
+
+impl PhongMaterial {
+    pub fn new(
+        device: &wgpu::Device,
+        name: String,
+        texture: Texture,
+        ambient: glam::Vec3,
+        diffuse: glam::Vec3,
+        specular: glam::Vec3,
+        shininess: f32,
+    ) -> Self {
+        let uniform = MaterialUniform {
+            ambient: ambient.to_array(),
+            _padding0: 0.0,
+            diffuse: diffuse.to_array(),
+            _padding1: 0.0,
+            specular: specular.to_array(),
+            shininess,
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Material Buffer", name)),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Material Bind Group"),
+            layout: &Self::bind_group_layout(device),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        PhongMaterial {
+            name,
+            texture,
+            ambient,
+            diffuse,
+            specular,
+            shininess,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Material Bind Group Layout"),
+            entries: &[
+                texture_entry(0),
+                sampler_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        })
+    }
+}
+
+// Follows the glTF metallic-roughness model, where every texture is scaled by its factor.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PbrFactors {
+    pub base_color: glam::Vec4,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: glam::Vec3,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+}
+
+impl Default for PbrFactors {
+    fn default() -> Self {
+        PbrFactors {
+            base_color: glam::Vec4::ONE,
+            metallic: 1.0,
+            roughness: 1.0,
+            emissive: glam::Vec3::ZERO,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+        }
+    }
+}
+
+// Missing textures are replaced by neutral ones, so only the factors take effect.
+#[derive(Debug, Default)]
+pub struct PbrTextures {
+    pub base_color: Option<Texture>,
+    // Roughness in the green channel and metallic in the blue one, stored linearly.
+    pub metallic_roughness: Option<Texture>,
+    // Tangent-space normals, stored linearly.
+    pub normal: Option<Texture>,
+    // Read from the red channel.
+    pub occlusion: Option<Texture>,
+    pub emissive: Option<Texture>,
+}
+
+#[derive(Debug)]
+pub struct PbrMaterial {
+    pub name: String,
+    pub factors: PbrFactors,
+    pub textures: PbrTextures,
+    // Shared by every missing slot of the same kind.
+    pub placeholders: PbrPlaceholders,
+    pub uniform_buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PbrMaterialUniform {
+    base_color: [f32; 4],
+    emissive: [f32; 3],
+    metallic: f32,
+    roughness: f32,
+    normal_scale: f32,
+    occlusion_strength: f32,
+    _padding: f32,
+}
+
+impl PbrMaterial {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        name: String,
+        factors: PbrFactors,
+        textures: PbrTextures,
+    ) -> Self {
+        let placeholders = PbrPlaceholders {
+            white: [
+                &textures.base_color,
+                &textures.metallic_roughness,
+                &textures.occlusion,
+                &textures.emissive,
+            ]
+            .iter()
+            .any(|texture| texture.is_none())
+            .then(|| Texture::white(device, queue)),
+            flat_normal: textures
+                .normal
+                .is_none()
+                .then(|| Texture::flat_normal(device, queue)),
+        };
+
+        let uniform = PbrMaterialUniform {
+            base_color: factors.base_color.to_array(),
+            emissive: factors.emissive.to_array(),
+            metallic: factors.metallic,
+            roughness: factors.roughness,
+            normal_scale: factors.normal_scale,
+            occlusion_strength: factors.occlusion_strength,
+            _padding: 0.0,
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} PBR Material Buffer", name)),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let slots = [
+            (&textures.base_color, &placeholders.white),
+            (&textures.metallic_roughness, &placeholders.white),
+            (&textures.normal, &placeholders.flat_normal),
+            (&textures.occlusion, &placeholders.white),
+            (&textures.emissive, &placeholders.white),
+        ];
+        let mut entries = Vec::new();
+        for (i, (texture, placeholder)) in slots.into_iter().enumerate() {
+            // A placeholder exists for every slot that was left empty.
+            let texture = texture.as_ref().or(placeholder.as_ref()).unwrap();
+            entries.push(wgpu::BindGroupEntry {
+                binding: i as u32 * 2,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: i as u32 * 2 + 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            });
+        }
+        entries.push(wgpu::BindGroupEntry {
+            binding: 10,
+            resource: uniform_buffer.as_entire_binding(),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("PBR Material Bind Group"),
+            layout: &Self::bind_group_layout(device),
+            entries: &entries,
+        });
+
+        PbrMaterial {
+            name,
+            factors,
+            textures,
+            placeholders,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let mut entries = Vec::new();
+        for i in 0..5 {
+            entries.push(texture_entry(i * 2));
+            entries.push(sampler_entry(i * 2 + 1));
+        }
+        entries.push(wgpu::BindGroupLayoutEntry {
+            binding: 10,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        });
+
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("PBR Material Bind Group Layout"),
+            entries: &entries,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct PbrPlaceholders {
+    pub white: Option<Texture>,
+    pub flat_normal: Option<Texture>,
+}
+
+fn texture_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
+fn sampler_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    }
+}
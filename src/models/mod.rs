@@ -0,0 +1,257 @@
+use std::path::Path;
+use wgpu::util::DeviceExt;
+
+use crate::error::{Error, Result};
+use crate::resources::{FileResolver, ResourceResolver};
+
+pub mod gltf;
+mod material;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+
+pub use self::gltf::{load_gltf, load_gltf_from_bytes};
+pub use material::{
+    Material, MaterialUniform, PbrFactors, PbrMaterial, PbrMaterialUniform, PbrPlaceholders,
+    PbrTextures, PhongMaterial,
+};
+pub use obj::{load_obj, load_obj_from_bytes};
+pub use ply::{load_ply, load_ply_from_bytes};
+pub use stl::{load_stl, load_stl_from_bytes};
+
+pub fn load_model(file_path: &str, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Model> {
+    load_model_with(file_path, &FileResolver::default(), device, queue)
+}
+
+pub fn load_model_with(
+    file_path: &str,
+    resolver: &dyn ResourceResolver,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> Result<Model> {
+    let bytes = read_resource(resolver, Path::new(file_path))?;
+    load_model_from_bytes(file_path, &bytes, resolver, device, queue)
+}
+
+// The name picks the format and is the base that related files are resolved against.
+pub fn load_model_from_bytes(
+    file_path: &str,
+    bytes: &[u8],
+    resolver: &dyn ResourceResolver,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> Result<Model> {
+    let extension = Path::new(file_path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+
+    match extension.as_deref() {
+        Some("obj") => load_obj_from_bytes(file_path, bytes, resolver, device, queue),
+        Some("gltf" | "glb") => load_gltf_from_bytes(file_path, bytes, resolver, device, queue),
+        Some("stl") => load_stl_from_bytes(file_path, bytes, device, queue),
+        Some("ply") => load_ply_from_bytes(file_path, bytes, device, queue),
+        _ => Err(Error::UnsupportedFormat {
+            path: file_path.into(),
+        }),
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Vertex {
+    position: [f32; 3],
+    tex_coords: [f32; 2],
+    normal: [f32; 3],
+    color: [f32; 4],
+}
+
+impl Vertex {
+    pub fn new(position: [f32; 3], tex_coords: [f32; 2], normal: [f32; 3]) -> Self {
+        Vertex {
+            position,
+            tex_coords,
+            normal,
+            color: [1.0; 4],
+        }
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Model {
+    pub mesh: Vec<Mesh>,
+    pub material: Vec<Material>,
+    pub bounds: Bounds,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: glam::Vec3,
+    pub max: glam::Vec3,
+}
+
+impl Bounds {
+    pub const EMPTY: Self = Bounds {
+        min: glam::Vec3::splat(f32::INFINITY),
+        max: glam::Vec3::splat(f32::NEG_INFINITY),
+    };
+
+    pub fn from_points(points: impl IntoIterator<Item = glam::Vec3>) -> Self {
+        points
+            .into_iter()
+            .fold(Self::EMPTY, |bounds, point| Bounds {
+                min: bounds.min.min(point),
+                max: bounds.max.max(point),
+            })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    pub fn union(&self, other: &Bounds) -> Self {
+        Bounds {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn center(&self) -> glam::Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn radius(&self) -> f32 {
+        (self.max - self.min).length() / 2.0
+    }
+
+    pub fn transformed(&self, matrix: glam::Mat4) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+
+        Self::from_points((0..8).map(|i| {
+            let corner = glam::Vec3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            );
+            matrix.transform_point3(corner)
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub struct Mesh {
+    pub name: String,
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub num_elements: u32,
+    pub material: usize,
+}
+
+impl Mesh {
+    pub fn new(
+        device: &wgpu::Device,
+        name: &str,
+        vertices: &[Vertex],
+        indices: &[u32],
+        material: usize,
+    ) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Vertex Buffer", name)),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Index Buffer", name)),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Mesh {
+            name: name.to_string(),
+            vertex_buffer,
+            index_buffer,
+            num_elements: indices.len() as u32,
+            material,
+        }
+    }
+}
+
+impl Model {
+    pub fn from_vertices(
+        device: &wgpu::Device,
+        name: &str,
+        vertices: &[Vertex],
+        indices: &[u32],
+        material: Material,
+    ) -> Self {
+        Model {
+            mesh: vec![Mesh::new(device, name, vertices, indices, 0)],
+            material: vec![material],
+            bounds: Bounds::from_points(vertices.iter().map(|v| glam::Vec3::from(v.position))),
+        }
+    }
+}
+
+pub(crate) fn read_resource(resolver: &dyn ResourceResolver, path: &Path) -> Result<Vec<u8>> {
+    resolver.read(path).map_err(|source| Error::Io {
+        path: path.into(),
+        source,
+    })
+}
+
+pub(crate) const DEFAULT_SHININESS: f32 = 32.0;
+
+pub(crate) fn compute_normals(positions: &[f32], indices: &[u32]) -> Vec<f32> {
+    let position = |i: u32| glam::Vec3::from_slice(&positions[i as usize * 3..]);
+
+    let mut normals = vec![glam::Vec3::ZERO; positions.len() / 3];
+    for face in indices.chunks_exact(3) {
+        let (a, b, c) = (position(face[0]), position(face[1]), position(face[2]));
+        // Left unnormalized so larger faces contribute more to shared vertices.
+        let normal = (b - a).cross(c - a);
+        for &i in face {
+            normals[i as usize] += normal;
+        }
+    }
+
+    normals
+        .into_iter()
+        .flat_map(|n| n.normalize_or_zero().to_array())
+        .collect()
+}
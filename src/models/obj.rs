@@ -0,0 +1,637 @@
+use std::path::{Path, PathBuf};
+
+use super::{
+    compute_normals, read_resource, Bounds, Material, Mesh, Model, PbrFactors, PbrTextures, Vertex,
+    DEFAULT_SHININESS,
+};
+use crate::error::{Error, Result};
+use crate::resources::{FileResolver, ResourceResolver};
+use crate::texture::{MipmapGenerator, SamplerOptions, Texture};
+
+pub fn load_obj(file_path: &str, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Model> {
+    let resolver = FileResolver::default();
+    let bytes = read_resource(&resolver, Path::new(file_path))?;
+    load_obj_from_bytes(file_path, &bytes, &resolver, device, queue)
+}
+
+pub fn load_obj_from_bytes(
+    file_path: &str,
+    bytes: &[u8],
+    resolver: &dyn ResourceResolver,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> Result<Model> {
+    let base = Path::new(file_path).parent().unwrap_or(Path::new(""));
+    // tobj only takes its own error type, so keep the real one to report it afterwards.
+    let read_error = std::cell::RefCell::new(None);
+    let missing_mtl = std::cell::RefCell::new(None);
+    let (models, materials) = tobj::load_obj_buf(
+        &mut std::io::Cursor::new(bytes),
+        &tobj::LoadOptions {
+            triangulate: true,
+            single_index: true,
+            ..Default::default()
+        },
+        |mtl_path| {
+            let path = base.join(mtl_path);
+            let mtl = resolver.read(&path).map_err(|source| {
+                if source.kind() == std::io::ErrorKind::NotFound {
+                    missing_mtl.replace(Some(path.clone()));
+                    return tobj::LoadError::OpenFileFailed;
+                }
+                read_error.replace(Some(Error::Io { path, source }));
+                tobj::LoadError::ReadError
+            })?;
+            tobj::load_mtl_buf(&mut std::io::Cursor::new(mtl))
+        },
+    )
+    .map_err(|source| Error::Parse {
+        path: file_path.into(),
+        source,
+    })?;
+    if let Some(err) = read_error.into_inner() {
+        return Err(err);
+    }
+    let materials = match materials {
+        Ok(materials) => materials,
+        // A missing .mtl file falls back to the default material.
+        Err(tobj::LoadError::OpenFileFailed) => {
+            if let Some(mtl_path) = missing_mtl.into_inner() {
+                log::warn!(
+                    "material library {} for {} was not found, using the default material",
+                    mtl_path.display(),
+                    file_path
+                );
+            }
+            Vec::new()
+        }
+        Err(source) => {
+            return Err(Error::Parse {
+                path: file_path.into(),
+                source,
+            })
+        }
+    };
+
+    let file_path = Path::new(file_path);
+    Model::new(models, file_path, resolver, device, queue, materials)
+}
+
+impl Model {
+    pub fn new(
+        models: Vec<tobj::Model>,
+        file_path: &Path,
+        resolver: &dyn ResourceResolver,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        model_materials: Vec<tobj::Material>,
+    ) -> Result<Self> {
+        let mipmaps = MipmapGenerator::new();
+        let mut materials = Vec::new();
+        for m in model_materials {
+            if is_pbr(&m) {
+                materials.push(pbr_material(
+                    device, queue, &mipmaps, resolver, file_path, m,
+                )?);
+                continue;
+            }
+
+            let texture = match &m.diffuse_texture {
+                Some(texture_map) => {
+                    let texture_map = TextureMap::parse(texture_map);
+                    let image = read_image(resolver, file_path, &m.name, &texture_map)?;
+                    Texture::from_image_with_format(
+                        device,
+                        queue,
+                        &mipmaps,
+                        &image,
+                        "Texture",
+                        &texture_map.sampler(),
+                        wgpu::TextureFormat::Rgba8UnormSrgb,
+                    )
+                }
+                None => Texture::white(device, queue),
+            };
+
+            let diffuse = m.diffuse.map(glam::Vec3::from).unwrap_or(glam::Vec3::ONE);
+            materials.push(Material::new(
+                device,
+                m.name,
+                texture,
+                m.ambient.map(glam::Vec3::from).unwrap_or(diffuse),
+                diffuse,
+                m.specular.map(glam::Vec3::from).unwrap_or(glam::Vec3::ZERO),
+                m.shininess.unwrap_or(DEFAULT_SHININESS),
+            ));
+        }
+
+        let default_material = materials.len();
+        if models.iter().any(|m| m.mesh.material_id.is_none()) {
+            materials.push(Material::default_material(device, queue));
+        }
+
+        let bounds = Bounds::from_points(
+            models
+                .iter()
+                .flat_map(|m| m.mesh.positions.chunks_exact(3).map(glam::Vec3::from_slice)),
+        );
+
+        let label = file_path.to_string_lossy();
+        let meshes = models
+            .into_iter()
+            .map(|m| {
+                let material = m.mesh.material_id.unwrap_or(default_material);
+                if material >= materials.len() {
+                    return Err(Error::MissingMaterial {
+                        path: file_path.into(),
+                        material,
+                    });
+                }
+
+                let normals = if m.mesh.normals.len() == m.mesh.positions.len() {
+                    m.mesh.normals
+                } else {
+                    compute_normals(&m.mesh.positions, &m.mesh.indices)
+                };
+
+                let vertices = (0..m.mesh.positions.len() / 3)
+                    .map(|i| {
+                        Vertex::new(
+                            [
+                                m.mesh.positions[i * 3],
+                                m.mesh.positions[i * 3 + 1],
+                                m.mesh.positions[i * 3 + 2],
+                            ],
+                            match m.mesh.texcoords.get(i * 2..i * 2 + 2) {
+                                Some(uv) => [uv[0], 1.0 - uv[1]],
+                                None => [0.0, 0.0],
+                            },
+                            [normals[i * 3], normals[i * 3 + 1], normals[i * 3 + 2]],
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                Ok(Mesh::new(
+                    device,
+                    &label,
+                    &vertices,
+                    &m.mesh.indices,
+                    material,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Model {
+            mesh: meshes,
+            material: materials,
+            bounds,
+        })
+    }
+}
+
+// The PBR extension to MTL adds roughness (`Pr`) and metallic (`Pm`) values and maps.
+fn is_pbr(material: &tobj::Material) -> bool {
+    ["Pr", "Pm", "map_Pr", "map_Pm"]
+        .iter()
+        .any(|key| material.unknown_param.contains_key(*key))
+}
+
+fn pbr_material(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    mipmaps: &MipmapGenerator,
+    resolver: &dyn ResourceResolver,
+    file_path: &Path,
+    m: tobj::Material,
+) -> Result<Material> {
+    let param = |key: &str| m.unknown_param.get(key).map(String::as_str);
+    let float = |key: &str| param(key).and_then(|value| value.trim().parse::<f32>().ok());
+    let map = |key: &str| param(key).map(TextureMap::parse);
+
+    let mut textures = PbrTextures::default();
+    let mut factors = PbrFactors {
+        base_color: glam::Vec3::from(m.diffuse.unwrap_or([1.0; 3]))
+            .extend(m.dissolve.unwrap_or(1.0)),
+        metallic: float("Pm").unwrap_or(0.0),
+        roughness: float("Pr").unwrap_or(1.0),
+        ..Default::default()
+    };
+
+    if let Some(texture_map) = m.diffuse_texture.as_deref().map(TextureMap::parse) {
+        let image = read_image(resolver, file_path, &m.name, &texture_map)?;
+        textures.base_color = Some(Texture::from_image_with_format(
+            device,
+            queue,
+            mipmaps,
+            &image,
+            "Texture",
+            &texture_map.sampler(),
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        ));
+    }
+
+    // Roughness and metallic come as separate grayscale maps, but share one texture on the GPU.
+    let roughness_map = map("map_Pr");
+    let metallic_map = map("map_Pm");
+    if roughness_map.is_some() || metallic_map.is_some() {
+        let read = |texture_map: &Option<TextureMap>| {
+            texture_map
+                .as_ref()
+                .map(|texture_map| read_image(resolver, file_path, &m.name, texture_map))
+                .transpose()
+        };
+        let roughness = read(&roughness_map)?.map(|image| image.to_luma8());
+        let metallic = read(&metallic_map)?.map(|image| image.to_luma8());
+        if roughness_map.is_some() && float("Pr").is_none() {
+            factors.roughness = 1.0;
+        }
+        if metallic_map.is_some() && float("Pm").is_none() {
+            factors.metallic = 1.0;
+        }
+
+        let (width, height) = [&roughness, &metallic]
+            .into_iter()
+            .flatten()
+            .fold((1, 1), |(width, height), image| {
+                (width.max(image.width()), height.max(image.height()))
+            });
+        let resize = |image: Option<image::GrayImage>| {
+            image.map(|image| {
+                if image.dimensions() == (width, height) {
+                    image
+                } else {
+                    image::imageops::resize(
+                        &image,
+                        width,
+                        height,
+                        image::imageops::FilterType::Triangle,
+                    )
+                }
+            })
+        };
+        let (roughness, metallic) = (resize(roughness), resize(metallic));
+        let combined = image::RgbaImage::from_fn(width, height, |x, y| {
+            let channel = |image: &Option<image::GrayImage>| {
+                image.as_ref().map_or(255, |image| image[(x, y)][0])
+            };
+            image::Rgba([255, channel(&roughness), channel(&metallic), 255])
+        });
+
+        let sampler = roughness_map
+            .as_ref()
+            .or(metallic_map.as_ref())
+            .map(TextureMap::sampler)
+            .unwrap_or_default();
+        textures.metallic_roughness = Some(Texture::from_image_with_format(
+            device,
+            queue,
+            mipmaps,
+            &image::DynamicImage::ImageRgba8(combined),
+            "Metallic Roughness Texture",
+            &sampler,
+            wgpu::TextureFormat::Rgba8Unorm,
+        ));
+    }
+
+    // `bump` and `map_Bump` are height maps in classic MTL, so only `norm` is a normal map.
+    if let Some(texture_map) = map("norm") {
+        let image = read_image(resolver, file_path, &m.name, &texture_map)?;
+        textures.normal = Some(Texture::from_image_with_format(
+            device,
+            queue,
+            mipmaps,
+            &image,
+            "Normal Texture",
+            &texture_map.sampler(),
+            wgpu::TextureFormat::Rgba8Unorm,
+        ));
+        factors.normal_scale = texture_map.bump_multiplier;
+    }
+
+    let emissive = param("Ke").and_then(parse_color);
+    factors.emissive = emissive.unwrap_or(glam::Vec3::ZERO);
+    if let Some(texture_map) = map("map_Ke") {
+        let image = read_image(resolver, file_path, &m.name, &texture_map)?;
+        textures.emissive = Some(Texture::from_image_with_format(
+            device,
+            queue,
+            mipmaps,
+            &image,
+            "Emissive Texture",
+            &texture_map.sampler(),
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        ));
+        if emissive.is_none() {
+            factors.emissive = glam::Vec3::ONE;
+        }
+    }
+
+    Ok(Material::pbr(device, queue, m.name, factors, textures))
+}
+
+fn parse_color(value: &str) -> Option<glam::Vec3> {
+    let components = value
+        .split_whitespace()
+        .map(str::parse::<f32>)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .ok()?;
+    match components[..] {
+        [r, g, b] => Some(glam::Vec3::new(r, g, b)),
+        [value] => Some(glam::Vec3::splat(value)),
+        _ => None,
+    }
+}
+
+struct TextureMap {
+    path: String,
+    clamp: bool,
+    bump_multiplier: f32,
+}
+
+impl TextureMap {
+    // tobj keeps the whole map_Kd statement, so strip the options in front of the file name.
+    fn parse(statement: &str) -> Self {
+        let mut clamp = false;
+        let mut bump_multiplier = 1.0;
+        let mut tokens = statement.split_whitespace().peekable();
+        while let Some(&option) = tokens.peek() {
+            let arguments = match option {
+                "-blendu" | "-blendv" | "-cc" | "-clamp" | "-imfchan" | "-texres" | "-bm"
+                | "-boost" | "-type" => 1,
+                "-mm" => 2,
+                "-o" | "-s" | "-t" => 3,
+                _ => break,
+            };
+            tokens.next();
+
+            for i in 0..arguments {
+                let Some(&argument) = tokens.peek() else {
+                    break;
+                };
+                // Offsets and scales take one to three numbers.
+                if i > 0 && argument.parse::<f32>().is_err() {
+                    break;
+                }
+                match option {
+                    "-clamp" => clamp = argument == "on",
+                    "-bm" => bump_multiplier = argument.parse().unwrap_or(1.0),
+                    _ => {}
+                }
+                tokens.next();
+            }
+        }
+
+        TextureMap {
+            path: tokens.collect::<Vec<_>>().join(" "),
+            clamp,
+            bump_multiplier,
+        }
+    }
+
+    fn sampler(&self) -> SamplerOptions {
+        SamplerOptions::default().with_address_mode(if self.clamp {
+            wgpu::AddressMode::ClampToEdge
+        } else {
+            wgpu::AddressMode::Repeat
+        })
+    }
+}
+
+fn read_image(
+    resolver: &dyn ResourceResolver,
+    file_path: &Path,
+    material: &str,
+    texture_map: &TextureMap,
+) -> Result<image::DynamicImage> {
+    let bytes = read_texture(resolver, file_path, material, &texture_map.path)?;
+    Ok(image::load_from_memory(&bytes)?)
+}
+
+fn read_texture(
+    resolver: &dyn ResourceResolver,
+    file_path: &Path,
+    material: &str,
+    texture: &str,
+) -> Result<Vec<u8>> {
+    let searched = texture_candidates(file_path, texture);
+    for candidate in &searched {
+        match resolver.read(candidate) {
+            Ok(bytes) => return Ok(bytes),
+            Err(source) if source.kind() == std::io::ErrorKind::NotFound => {}
+            Err(source) => {
+                return Err(Error::Io {
+                    path: candidate.clone(),
+                    source,
+                })
+            }
+        }
+    }
+
+    Err(Error::MissingTexture {
+        path: file_path.into(),
+        material: material.to_string(),
+        texture: texture.into(),
+        searched,
+    })
+}
+
+fn texture_candidates(file_path: &Path, texture: &str) -> Vec<PathBuf> {
+    // MTL files exported on Windows use backslashes, which are plain characters elsewhere.
+    let normalized = texture.replace('\\', "/");
+    let texture = Path::new(&normalized);
+    let base = file_path.parent().unwrap_or(Path::new(""));
+
+    let mut candidates = Vec::new();
+    if texture.is_absolute() || has_drive_prefix(&normalized) {
+        candidates.push(texture.to_path_buf());
+    } else {
+        candidates.push(base.join(texture));
+    }
+    // Exporters often write paths from the artist's machine, so also look next to the model.
+    if let Some(name) = texture.file_name() {
+        let fallback = base.join(name);
+        if !candidates.contains(&fallback) {
+            candidates.push(fallback);
+        }
+    }
+    candidates
+}
+
+fn has_drive_prefix(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'/'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn material(mtl: &str) -> tobj::Material {
+        let (mut materials, _) =
+            tobj::load_mtl_buf(&mut std::io::Cursor::new(format!("newmtl test\n{}", mtl))).unwrap();
+        materials.remove(0)
+    }
+
+    #[test]
+    fn parses_emissive_colors() {
+        assert_eq!(parse_color("1 0.5 0"), Some(glam::Vec3::new(1.0, 0.5, 0.0)));
+        assert_eq!(parse_color(" 0.25 "), Some(glam::Vec3::splat(0.25)));
+    }
+
+    #[test]
+    fn rejects_malformed_emissive_colors() {
+        assert_eq!(parse_color(""), None);
+        assert_eq!(parse_color("1 0.5"), None);
+        assert_eq!(parse_color("1 0.5 0 1"), None);
+        assert_eq!(parse_color("1 red 0"), None);
+    }
+
+    #[test]
+    fn detects_pbr_materials() {
+        for mtl in ["Pr 0.5", "Pm 1", "map_Pr rough.png", "map_Pm metal.png"] {
+            assert!(is_pbr(&material(mtl)), "{:?} should be PBR", mtl);
+        }
+    }
+
+    #[test]
+    fn keeps_classic_materials_phong() {
+        let classic = material("Kd 1 1 1\nKs 0.5 0.5 0.5\nNs 32\nmap_Bump height.png\n");
+        assert!(!is_pbr(&classic));
+    }
+
+    #[test]
+    fn parses_bump_multiplier() {
+        let texture_map = TextureMap::parse("-bm 0.5 normal.png");
+        assert_eq!(texture_map.path, "normal.png");
+        assert_eq!(texture_map.bump_multiplier, 0.5);
+        assert_eq!(TextureMap::parse("normal.png").bump_multiplier, 1.0);
+    }
+
+    #[test]
+    fn strips_texture_options() {
+        let clamped = TextureMap::parse("-clamp on wood.png");
+        assert_eq!(clamped.path, "wood.png");
+        assert!(clamped.clamp);
+        assert!(!TextureMap::parse("-clamp off wood.png").clamp);
+
+        assert_eq!(TextureMap::parse("-s 1 1 1 wood.png").path, "wood.png");
+        // Scales may leave out the trailing numbers.
+        assert_eq!(TextureMap::parse("-s 2 wood.png").path, "wood.png");
+        assert_eq!(
+            TextureMap::parse("-o 0.5 0.5 -blendu off wood.png").path,
+            "wood.png"
+        );
+    }
+
+    #[test]
+    fn keeps_spaces_in_texture_paths() {
+        assert_eq!(TextureMap::parse("old wood.png").path, "old wood.png");
+        let texture_map = TextureMap::parse("-clamp on -mm 0 1 textures/old wood.png");
+        assert_eq!(texture_map.path, "textures/old wood.png");
+        assert!(texture_map.clamp);
+    }
+
+    #[test]
+    fn reports_unreadable_material_libraries() {
+        let Some(state) = crate::state::headless_test_state() else {
+            return;
+        };
+        let resolver = |path: &Path| -> std::io::Result<Vec<u8>> {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("{} is locked", path.display()),
+            ))
+        };
+        let obj = b"mtllib cube.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        let result = load_obj_from_bytes(
+            "models/cube.obj",
+            obj,
+            &resolver,
+            &state.device,
+            &state.queue,
+        );
+        match result {
+            Err(Error::Io { path, source }) => {
+                assert_eq!(path, Path::new("models/cube.mtl"));
+                assert_eq!(source.kind(), std::io::ErrorKind::PermissionDenied);
+            }
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("loaded a model without its material library"),
+        }
+    }
+
+    #[test]
+    fn falls_back_to_the_default_material_without_a_material_library() {
+        let Some(state) = crate::state::headless_test_state() else {
+            return;
+        };
+        let obj = b"mtllib missing.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        let model = load_obj_from_bytes(
+            "cube.obj",
+            obj,
+            &crate::resources::MemoryResolver::new(),
+            &state.device,
+            &state.queue,
+        )
+        .unwrap();
+        assert_eq!(model.material.len(), 1);
+        assert_eq!(model.material[0].name(), "Default");
+    }
+
+    fn candidates(file_path: &str, texture: &str) -> Vec<PathBuf> {
+        texture_candidates(Path::new(file_path), texture)
+    }
+
+    #[test]
+    fn resolves_relative_textures_next_to_the_model() {
+        assert_eq!(
+            candidates("models/cube.obj", "textures/wood.png"),
+            [
+                PathBuf::from("models/textures/wood.png"),
+                PathBuf::from("models/wood.png")
+            ]
+        );
+        assert_eq!(
+            candidates("models/cube.obj", "wood.png"),
+            [PathBuf::from("models/wood.png")]
+        );
+    }
+
+    #[test]
+    fn converts_backslash_paths() {
+        assert_eq!(
+            candidates("models/cube.obj", "textures\\wood.png"),
+            [
+                PathBuf::from("models/textures/wood.png"),
+                PathBuf::from("models/wood.png")
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_absolute_paths_with_a_basename_fallback() {
+        assert_eq!(
+            candidates("models/cube.obj", "/home/artist/wood.png"),
+            [
+                PathBuf::from("/home/artist/wood.png"),
+                PathBuf::from("models/wood.png")
+            ]
+        );
+        assert_eq!(
+            candidates("models/cube.obj", "C:\\Users\\artist\\wood.png"),
+            [
+                PathBuf::from("C:/Users/artist/wood.png"),
+                PathBuf::from("models/wood.png")
+            ]
+        );
+    }
+
+    #[test]
+    fn detects_drive_prefixes() {
+        assert!(has_drive_prefix("C:/wood.png"));
+        assert!(has_drive_prefix("d:/textures/wood.png"));
+        assert!(!has_drive_prefix("C:wood.png"));
+        assert!(!has_drive_prefix("textures/wood.png"));
+        assert!(!has_drive_prefix("1:/wood.png"));
+    }
+}
@@ -0,0 +1,545 @@
+use glam::Vec3;
+
+use std::path::Path;
+
+use super::{compute_normals, read_resource, Material, Model, Vertex};
+use crate::error::{Error, Result};
+use crate::resources::FileResolver;
+
+pub fn load_ply(file_path: &str, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Model> {
+    let bytes = read_resource(&FileResolver::default(), Path::new(file_path))?;
+    load_ply_from_bytes(file_path, &bytes, device, queue)
+}
+
+pub fn load_ply_from_bytes(
+    file_path: &str,
+    bytes: &[u8],
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> Result<Model> {
+    let mesh = parse_ply(bytes).map_err(|message| Error::InvalidModel {
+        path: file_path.into(),
+        message,
+    })?;
+
+    let normals = if mesh.normals.is_empty() {
+        let flat = mesh
+            .positions
+            .iter()
+            .flat_map(|p| p.to_array())
+            .collect::<Vec<_>>();
+        compute_normals(&flat, &mesh.indices)
+            .chunks_exact(3)
+            .map(Vec3::from_slice)
+            .collect()
+    } else {
+        mesh.normals
+    };
+
+    let vertices = mesh
+        .positions
+        .iter()
+        .enumerate()
+        .map(|(i, position)| {
+            let vertex = Vertex::new(
+                position.to_array(),
+                mesh.tex_coords.get(i).copied().unwrap_or_default(),
+                normals.get(i).copied().unwrap_or(Vec3::Y).to_array(),
+            );
+            match mesh.colors.get(i) {
+                Some(&color) => vertex.with_color(color),
+                None => vertex,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Vertex colors already carry the albedo, so keep the material neutral.
+    let material = if mesh.colors.is_empty() {
+        Material::default_material(device, queue)
+    } else {
+        Material::from_color(device, queue, "Vertex Color", Vec3::ONE)
+    };
+
+    Ok(Model::from_vertices(
+        device,
+        file_path,
+        &vertices,
+        &mesh.indices,
+        material,
+    ))
+}
+
+#[derive(Default)]
+struct PlyMesh {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    colors: Vec<[f32; 4]>,
+    tex_coords: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Copy, Clone)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> std::result::Result<Self, String> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return Err(format!("unknown property type {:?}", name)),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    // Integer color channels span the full range of their type.
+    fn normalize(self, value: f64) -> f32 {
+        let max = match self {
+            Scalar::I8 => i8::MAX as f64,
+            Scalar::U8 => u8::MAX as f64,
+            Scalar::I16 => i16::MAX as f64,
+            Scalar::U16 => u16::MAX as f64,
+            Scalar::I32 => i32::MAX as f64,
+            Scalar::U32 => u32::MAX as f64,
+            Scalar::F32 | Scalar::F64 => 1.0,
+        };
+        (value / max) as f32
+    }
+}
+
+enum PropertyKind {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn property(&self, names: &[&str]) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|property| names.contains(&property.name.as_str()))
+    }
+
+    fn scalar(&self, index: usize) -> Scalar {
+        match self.properties[index].kind {
+            PropertyKind::Scalar(scalar) => scalar,
+            PropertyKind::List { item, .. } => item,
+        }
+    }
+}
+
+enum Value {
+    Scalar(f64),
+    List(Vec<f64>),
+}
+
+impl Value {
+    fn scalar(&self) -> f64 {
+        match self {
+            Value::Scalar(value) => *value,
+            Value::List(values) => values.first().copied().unwrap_or_default(),
+        }
+    }
+}
+
+struct Body<'a> {
+    format: Format,
+    bytes: &'a [u8],
+    tokens: std::str::SplitAsciiWhitespace<'a>,
+}
+
+impl Body<'_> {
+    fn read(&mut self, scalar: Scalar) -> std::result::Result<f64, String> {
+        if self.format == Format::Ascii {
+            return self
+                .tokens
+                .next()
+                .and_then(|token| token.parse().ok())
+                .ok_or_else(|| "unexpected end of data".to_string());
+        }
+
+        let size = scalar.size();
+        if self.bytes.len() < size {
+            return Err("unexpected end of data".to_string());
+        }
+        let (head, rest) = self.bytes.split_at(size);
+        self.bytes = rest;
+
+        let mut raw = [0; 8];
+        raw[..size].copy_from_slice(head);
+        if self.format == Format::BinaryBigEndian {
+            raw[..size].reverse();
+        }
+        Ok(match scalar {
+            Scalar::I8 => raw[0] as i8 as f64,
+            Scalar::U8 => raw[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([raw[0], raw[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([raw[0], raw[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(raw),
+        })
+    }
+
+    fn read_row(&mut self, element: &Element) -> std::result::Result<Vec<Value>, String> {
+        element
+            .properties
+            .iter()
+            .map(|property| match property.kind {
+                PropertyKind::Scalar(scalar) => self.read(scalar).map(Value::Scalar),
+                PropertyKind::List { count, item } => {
+                    let count = self.read(count)? as usize;
+                    (0..count)
+                        .map(|_| self.read(item))
+                        .collect::<std::result::Result<_, _>>()
+                        .map(Value::List)
+                }
+            })
+            .collect()
+    }
+}
+
+fn parse_ply(bytes: &[u8]) -> std::result::Result<PlyMesh, String> {
+    const END_HEADER: &[u8] = b"end_header";
+
+    if !bytes.starts_with(b"ply") {
+        return Err("missing ply magic number".to_string());
+    }
+    let header_end = bytes
+        .windows(END_HEADER.len())
+        .position(|window| window == END_HEADER)
+        .ok_or_else(|| "missing end_header".to_string())?;
+    let body_start = bytes[header_end..]
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .map(|offset| header_end + offset + 1)
+        .unwrap_or(bytes.len());
+    let header = std::str::from_utf8(&bytes[..header_end])
+        .map_err(|_| "header is not valid text".to_string())?;
+
+    let mut format = None;
+    let mut elements = Vec::<Element>::new();
+    for line in header.lines() {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            ["format", name, _] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(format!("unknown format {:?}", name)),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| format!("invalid count for element {:?}", name))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => elements
+                .last_mut()
+                .ok_or_else(|| "property outside of an element".to_string())?
+                .properties
+                .push(Property {
+                    name: name.to_string(),
+                    kind: PropertyKind::List {
+                        count: Scalar::parse(count)?,
+                        item: Scalar::parse(item)?,
+                    },
+                }),
+            ["property", scalar, name] => elements
+                .last_mut()
+                .ok_or_else(|| "property outside of an element".to_string())?
+                .properties
+                .push(Property {
+                    name: name.to_string(),
+                    kind: PropertyKind::Scalar(Scalar::parse(scalar)?),
+                }),
+            _ => {}
+        }
+    }
+    let format = format.ok_or_else(|| "missing format line".to_string())?;
+
+    let body = &bytes[body_start..];
+    let text = match format {
+        Format::Ascii => {
+            std::str::from_utf8(body).map_err(|_| "ascii body is not valid text".to_string())?
+        }
+        _ => "",
+    };
+    let mut body = Body {
+        format,
+        bytes: body,
+        tokens: text.split_ascii_whitespace(),
+    };
+
+    let mut mesh = PlyMesh::default();
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(&mut body, element, &mut mesh)?,
+            "face" => read_faces(&mut body, element, &mut mesh)?,
+            // Other elements still have to be consumed to reach the ones after them.
+            _ => {
+                for _ in 0..element.count {
+                    body.read_row(element)?;
+                }
+            }
+        }
+    }
+
+    if mesh
+        .indices
+        .iter()
+        .any(|&i| i as usize >= mesh.positions.len())
+    {
+        return Err("face references a missing vertex".to_string());
+    }
+    Ok(mesh)
+}
+
+fn read_vertices(
+    body: &mut Body,
+    element: &Element,
+    mesh: &mut PlyMesh,
+) -> std::result::Result<(), String> {
+    let required = |name: &str| {
+        element
+            .property(&[name])
+            .ok_or_else(|| format!("vertex element has no {:?} property", name))
+    };
+    let position = [required("x")?, required("y")?, required("z")?];
+    let normal = [
+        element.property(&["nx"]),
+        element.property(&["ny"]),
+        element.property(&["nz"]),
+    ];
+    let color = [
+        element.property(&["red", "r"]),
+        element.property(&["green", "g"]),
+        element.property(&["blue", "b"]),
+    ];
+    let alpha = element.property(&["alpha", "a"]);
+    let tex_coords = [
+        element.property(&["s", "u", "texture_u"]),
+        element.property(&["t", "v", "texture_v"]),
+    ];
+
+    for _ in 0..element.count {
+        let row = body.read_row(element)?;
+        let value = |index: usize| row[index].scalar() as f32;
+        let channel = |index: usize| element.scalar(index).normalize(row[index].scalar());
+        let color_channel = |index: usize| match element.scalar(index) {
+            Scalar::F32 | Scalar::F64 => channel(index),
+            // Integer colors are stored like 8-bit images, in sRGB.
+            _ => srgb_to_linear(channel(index)),
+        };
+
+        mesh.positions.push(Vec3::from_array(position.map(value)));
+        if let [Some(x), Some(y), Some(z)] = normal {
+            mesh.normals.push(Vec3::new(value(x), value(y), value(z)));
+        }
+        if let [Some(r), Some(g), Some(b)] = color {
+            mesh.colors.push([
+                color_channel(r),
+                color_channel(g),
+                color_channel(b),
+                alpha.map_or(1.0, channel),
+            ]);
+        }
+        if let [Some(u), Some(v)] = tex_coords {
+            // PLY texture coordinates use a bottom-left origin.
+            mesh.tex_coords.push([value(u), 1.0 - value(v)]);
+        }
+    }
+    Ok(())
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn read_faces(
+    body: &mut Body,
+    element: &Element,
+    mesh: &mut PlyMesh,
+) -> std::result::Result<(), String> {
+    let indices = element
+        .property(&["vertex_indices", "vertex_index"])
+        .ok_or_else(|| "face element has no vertex_indices property".to_string())?;
+
+    for _ in 0..element.count {
+        let row = body.read_row(element)?;
+        let Value::List(face) = &row[indices] else {
+            return Err("vertex_indices is not a list".to_string());
+        };
+        // Polygons are triangulated as fans around their first vertex.
+        for i in 1..face.len().saturating_sub(1) {
+            mesh.indices
+                .extend([face[0] as u32, face[i] as u32, face[i + 1] as u32]);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "element vertex 4\n\
+        property float x\n\
+        property float y\n\
+        property float z\n\
+        property uchar red\n\
+        property uchar green\n\
+        property uchar blue\n\
+        element face 1\n\
+        property list uchar int vertex_indices\n\
+        end_header\n";
+
+    const POSITIONS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+
+    fn binary(format: &str, big_endian: bool) -> Vec<u8> {
+        let mut bytes = format!("ply\nformat {} 1.0\n{}", format, HEADER).into_bytes();
+        let mut push = |raw: &[u8]| {
+            let mut raw = raw.to_vec();
+            if big_endian {
+                raw.reverse();
+            }
+            bytes.extend(raw);
+        };
+        for position in POSITIONS {
+            for value in position {
+                push(&value.to_le_bytes());
+            }
+            push(&[255]);
+            push(&[128]);
+            push(&[0]);
+        }
+        push(&[4]);
+        for index in 0..4i32 {
+            push(&index.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn assert_quad(mesh: &PlyMesh) {
+        assert_eq!(mesh.positions, POSITIONS.map(Vec3::from_array));
+        assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.colors.len(), 4);
+        let [r, g, b, a] = mesh.colors[0];
+        assert_eq!((r, b, a), (1.0, 0.0, 1.0));
+        assert!((g - 0.2158).abs() < 1e-3, "green was {}", g);
+    }
+
+    #[test]
+    fn parses_ascii() {
+        let text = format!(
+            "ply\nformat ascii 1.0\n{}\
+            0 0 0 255 128 0\n\
+            1 0 0 255 128 0\n\
+            1 1 0 255 128 0\n\
+            0 1 0 255 128 0\n\
+            4 0 1 2 3\n",
+            HEADER
+        );
+        assert_quad(&parse_ply(text.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn parses_binary_little_endian() {
+        assert_quad(&parse_ply(&binary("binary_little_endian", false)).unwrap());
+    }
+
+    #[test]
+    fn parses_binary_big_endian() {
+        assert_quad(&parse_ply(&binary("binary_big_endian", true)).unwrap());
+    }
+
+    #[test]
+    fn triangulates_list_faces() {
+        let text = "ply\n\
+            format ascii 1.0\n\
+            element vertex 5\n\
+            property float x\n\
+            property float y\n\
+            property float z\n\
+            element face 2\n\
+            property list uint8 uint32 vertex_index\n\
+            end_header\n\
+            0 0 0\n1 0 0\n1 1 0\n0 1 0\n2 2 0\n\
+            3 0 1 2\n\
+            5 0 1 4 2 3\n";
+        let mesh = parse_ply(text.as_bytes()).unwrap();
+        assert_eq!(mesh.indices, [0, 1, 2, 0, 1, 4, 0, 4, 2, 0, 2, 3]);
+        assert!(mesh.colors.is_empty());
+    }
+
+    #[test]
+    fn rejects_truncated_file() {
+        let mut bytes = binary("binary_little_endian", false);
+        bytes.truncate(bytes.len() - 3);
+        assert_eq!(
+            parse_ply(&bytes).err().as_deref(),
+            Some("unexpected end of data")
+        );
+    }
+
+    #[test]
+    fn rejects_missing_vertex() {
+        let text = "ply\nformat ascii 1.0\n\
+            element vertex 1\nproperty float x\nproperty float y\nproperty float z\n\
+            element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+            0 0 0\n3 0 1 2\n";
+        assert!(parse_ply(text.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,188 @@
+use glam::Vec3;
+
+use std::path::Path;
+
+use super::{read_resource, Material, Model, Vertex};
+use crate::error::{Error, Result};
+use crate::resources::FileResolver;
+
+pub fn load_stl(file_path: &str, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Model> {
+    let bytes = read_resource(&FileResolver::default(), Path::new(file_path))?;
+    load_stl_from_bytes(file_path, &bytes, device, queue)
+}
+
+pub fn load_stl_from_bytes(
+    file_path: &str,
+    bytes: &[u8],
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> Result<Model> {
+    let triangles = parse_stl(bytes).map_err(|message| Error::InvalidModel {
+        path: file_path.into(),
+        message,
+    })?;
+
+    // Facets are not indexed, so every triangle gets its own flat-shaded vertices.
+    let vertices = triangles
+        .iter()
+        .flat_map(|triangle| {
+            let normal = (triangle[1] - triangle[0])
+                .cross(triangle[2] - triangle[0])
+                .normalize_or_zero();
+            triangle.iter().map(move |position| {
+                Vertex::new(position.to_array(), [0.0, 0.0], normal.to_array())
+            })
+        })
+        .collect::<Vec<_>>();
+    let indices = (0..vertices.len() as u32).collect::<Vec<_>>();
+
+    Ok(Model::from_vertices(
+        device,
+        file_path,
+        &vertices,
+        &indices,
+        Material::default_material(device, queue),
+    ))
+}
+
+fn parse_stl(bytes: &[u8]) -> std::result::Result<Vec<[Vec3; 3]>, String> {
+    const HEADER_LEN: usize = 80;
+    const TRIANGLE_LEN: usize = 50;
+
+    // Binary files may also start with "solid", so trust an exact size match first. Some
+    // exporters pad binary files, so a larger file counts too unless it reads as ASCII.
+    if let Some(count) = bytes.get(HEADER_LEN..HEADER_LEN + 4) {
+        let count = u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize;
+        let len = count
+            .checked_mul(TRIANGLE_LEN)
+            .and_then(|len| len.checked_add(HEADER_LEN + 4))
+            .filter(|&len| bytes.len() == len || (bytes.len() > len && !is_ascii_stl(bytes)));
+        if let Some(len) = len {
+            return Ok(bytes[HEADER_LEN + 4..len]
+                .chunks_exact(TRIANGLE_LEN)
+                .map(|triangle| {
+                    // Skip the stored facet normal, it is recomputed from the vertices.
+                    let vertex = |i: usize| {
+                        let offset = 12 + i * 12;
+                        Vec3::from_array(std::array::from_fn(|axis| {
+                            let start = offset + axis * 4;
+                            f32::from_le_bytes([
+                                triangle[start],
+                                triangle[start + 1],
+                                triangle[start + 2],
+                                triangle[start + 3],
+                            ])
+                        }))
+                    };
+                    [vertex(0), vertex(1), vertex(2)]
+                })
+                .collect());
+        }
+    }
+
+    let text = std::str::from_utf8(bytes).map_err(|_| "not a valid STL file".to_string())?;
+    if !text.trim_start().starts_with("solid") {
+        return Err("not a valid STL file".to_string());
+    }
+
+    let mut vertices = Vec::new();
+    let mut tokens = text.split_whitespace();
+    while let Some(token) = tokens.next() {
+        if token != "vertex" {
+            continue;
+        }
+        let mut coordinate = || -> std::result::Result<f32, String> {
+            tokens
+                .next()
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| "invalid vertex coordinate".to_string())
+        };
+        vertices.push(Vec3::new(coordinate()?, coordinate()?, coordinate()?));
+    }
+
+    if vertices.len() % 3 != 0 {
+        return Err("facet does not have three vertices".to_string());
+    }
+    Ok(vertices
+        .chunks_exact(3)
+        .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+        .collect())
+}
+
+// An ASCII body opens with "solid" and reaches its first facet soon after the name.
+fn is_ascii_stl(bytes: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(512)]);
+    let mut words = head.split_whitespace();
+    words.next() == Some("solid") && words.any(|word| word == "facet" || word == "endsolid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIANGLE: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 2.0]];
+
+    fn binary(header: &[u8], triangles: &[[[f32; 3]; 3]], padding: usize) -> Vec<u8> {
+        let mut bytes = header.to_vec();
+        bytes.resize(80, 0);
+        bytes.extend((triangles.len() as u32).to_le_bytes());
+        for triangle in triangles {
+            bytes.extend([0u8; 12]);
+            for value in triangle.iter().flatten() {
+                bytes.extend(value.to_le_bytes());
+            }
+            bytes.extend([0u8; 2]);
+        }
+        bytes.resize(bytes.len() + padding, 0);
+        bytes
+    }
+
+    fn expected() -> Vec<[Vec3; 3]> {
+        vec![TRIANGLE.map(Vec3::from_array)]
+    }
+
+    #[test]
+    fn parses_ascii() {
+        let text = "solid test\n\
+            facet normal 0 0 1\n\
+            outer loop\n\
+            vertex 0 0 0\n\
+            vertex 1 0 0\n\
+            vertex 0 1 2\n\
+            endloop\n\
+            endfacet\n\
+            endsolid test\n";
+        assert_eq!(parse_stl(text.as_bytes()).unwrap(), expected());
+    }
+
+    #[test]
+    fn parses_binary() {
+        let bytes = binary(b"exported", &[TRIANGLE], 0);
+        assert_eq!(parse_stl(&bytes).unwrap(), expected());
+    }
+
+    #[test]
+    fn parses_binary_with_solid_header() {
+        let bytes = binary(b"solid exported by a binary writer", &[TRIANGLE], 0);
+        assert_eq!(parse_stl(&bytes).unwrap(), expected());
+    }
+
+    #[test]
+    fn parses_padded_binary() {
+        let bytes = binary(b"solid padded", &[TRIANGLE], 16);
+        assert_eq!(parse_stl(&bytes).unwrap(), expected());
+    }
+
+    #[test]
+    fn rejects_incomplete_ascii_facet() {
+        let text = "solid test\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\n";
+        assert!(parse_stl(text.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_binary() {
+        let mut bytes = binary(b"exported", &[TRIANGLE, TRIANGLE], 0);
+        bytes.truncate(bytes.len() - 10);
+        assert!(parse_stl(&bytes).is_err());
+    }
+}
@@ -1,36 +1,172 @@
-use crate::models::Model;
+use crate::instances::{Instance, InstanceRaw, Instances};
+use crate::models::{Bounds, Material, Model};
+use crate::shadows::ShadowUniform;
 use crate::state::State;
+use std::collections::HashMap;
 use std::ops::Range;
+use std::sync::Arc;
 
-pub fn draw(state: &mut State) {
-    let frame = state.surface.get_current_texture().unwrap();
+pub fn draw(state: &mut State) -> crate::Result<()> {
+    let Some(surface) = &state.surface else {
+        return Ok(());
+    };
+    let frame = match surface.get_current_texture() {
+        Ok(frame) => frame,
+        // The next frame picks up the reconfigured surface.
+        Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+            surface.configure(&state.device, &state.surface_config);
+            return Ok(());
+        }
+        Err(wgpu::SurfaceError::Timeout) => return Ok(()),
+        Err(err @ wgpu::SurfaceError::OutOfMemory) => return Err(crate::Error::Surface(err)),
+    };
 
     let view = frame
         .texture
         .create_view(&wgpu::TextureViewDescriptor::default());
 
+    render(state, &view);
+
+    if let Some(path) = state.screenshot_path.take() {
+        let image = if frame
+            .texture
+            .usage()
+            .contains(wgpu::TextureUsages::COPY_SRC)
+        {
+            crate::texture::read_to_image(&state.device, &state.queue, &frame.texture)
+        } else {
+            draw_to_image(state)
+        };
+        let saved = image.and_then(|image| {
+            image.save(&path).map_err(|source| crate::Error::ImageSave {
+                path: path.clone(),
+                source,
+            })
+        });
+        if let Err(err) = saved {
+            log::error!("failed to capture screenshot: {}", err);
+        }
+    }
+
+    frame.present();
+    Ok(())
+}
+
+pub fn draw_to_image(state: &mut State) -> crate::Result<image::RgbaImage> {
+    let texture = state.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Offscreen Texture"),
+        size: wgpu::Extent3d {
+            width: state.surface_config.width,
+            height: state.surface_config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: state.surface_config.format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    render(state, &view);
+    crate::texture::read_to_image(&state.device, &state.queue, &texture)
+}
+
+fn render(state: &mut State, view: &wgpu::TextureView) {
     let mut encoder = state
         .device
         .create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Command Encoder"),
         });
 
+    let light_view_proj = shadow_view_proj(state);
+
+    let mut instances = Vec::with_capacity(state.instances.len());
+    let batches = batch_instances(&state.instances, &mut instances, |_| true);
+    let shadow_batches = match light_view_proj {
+        Some(_) => batch_instances(&state.instances, &mut instances, Instance::casts_shadows),
+        None => Vec::new(),
+    };
+    state.write_instance_buffer(&instances);
+
+    state
+        .shadow_map
+        .set_resolution(&state.device, state.shadows.map_resolution());
+    let shadow_uniform = match light_view_proj {
+        Some(view_proj) => {
+            ShadowUniform::new(view_proj, &state.shadows, state.shadow_map.resolution)
+        }
+        None => ShadowUniform::disabled(),
+    };
+    state.queue.write_buffer(
+        &state.shadow_map.buffer,
+        0,
+        bytemuck::cast_slice(&[shadow_uniform]),
+    );
+
     state.camera_uniform.update_view_proj(&state.camera);
     state.queue.write_buffer(
         &state.camera_buffer,
         0,
         bytemuck::cast_slice(&[state.camera_uniform]),
     );
+    state.queue.write_buffer(
+        &state.light_buffer,
+        0,
+        bytemuck::cast_slice(&[state.lights.to_uniform()]),
+    );
+
+    if light_view_proj.is_some() {
+        let mut shadow_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &state.shadow_map.texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        shadow_pass.set_pipeline(&state.shadow_map.pipeline);
+        shadow_pass.set_bind_group(0, &state.shadow_map.pass_bind_group, &[]);
+        shadow_pass.set_vertex_buffer(1, state.instance_buffer.slice(..));
+        for (model, range) in &shadow_batches {
+            for mesh in &model.mesh {
+                shadow_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                shadow_pass
+                    .set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                shadow_pass.draw_indexed(0..mesh.num_elements, 0, range.clone());
+            }
+        }
+    }
 
     {
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Vertex Render Pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
-                    store: wgpu::StoreOp::Store,
+            // With MSAA the samples are resolved into the target and then discarded.
+            color_attachments: &[Some(match &state.msaa_texture {
+                Some(msaa) => wgpu::RenderPassColorAttachment {
+                    view: &msaa.view,
+                    resolve_target: Some(view),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(state.clear_color),
+                        store: wgpu::StoreOp::Discard,
+                    },
+                },
+                None => wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(state.clear_color),
+                        store: wgpu::StoreOp::Store,
+                    },
                 },
             })],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
@@ -46,33 +182,98 @@ pub fn draw(state: &mut State) {
         });
 
         render_pass.set_bind_group(0, &state.camera_bind_group, &[]);
+        render_pass.set_bind_group(2, &state.light_bind_group, &[]);
+        render_pass.set_bind_group(3, &state.shadow_map.bind_group, &[]);
+        render_pass.set_vertex_buffer(1, state.instance_buffer.slice(..));
         render_pass.set_pipeline(&state.render_pipeline);
-        for i in &state.instances {
-            render_pass.draw_model(&i.model);
+        for (model, range) in &batches {
+            render_pass.draw_model_instanced_where(model, range.clone(), |m| !m.is_pbr());
+        }
+        render_pass.set_pipeline(&state.pbr_pipeline);
+        for (model, range) in &batches {
+            render_pass.draw_model_instanced_where(model, range.clone(), Material::is_pbr);
         }
     }
 
     state.queue.submit(Some(encoder.finish()));
-    frame.present();
+}
+
+type Batch = (Arc<Model>, Range<u32>);
+
+// Appends the instance data to `raw`, so several passes can share one instance buffer.
+fn batch_instances(
+    instances: &Instances,
+    raw: &mut Vec<InstanceRaw>,
+    filter: impl Fn(&Instance) -> bool,
+) -> Vec<Batch> {
+    let mut groups: Vec<(Arc<Model>, Vec<InstanceRaw>)> = Vec::new();
+    let mut lookup = HashMap::new();
+    for (_, instance) in instances.iter() {
+        let Some(model) = &instance.model else {
+            continue;
+        };
+        if !instance.is_visible_in_tree() || !filter(instance) {
+            continue;
+        }
+        let index = *lookup.entry(Arc::as_ptr(model)).or_insert_with(|| {
+            groups.push((model.clone(), Vec::new()));
+            groups.len() - 1
+        });
+        groups[index].1.push(instance.to_raw());
+    }
+
+    let mut batches = Vec::with_capacity(groups.len());
+    for (model, group) in groups {
+        let start = raw.len() as u32;
+        raw.extend(group);
+        batches.push((model, start..raw.len() as u32));
+    }
+    batches
+}
+
+// The light's view is fitted to everything visible, so receivers stay inside the shadow map.
+fn shadow_view_proj(state: &State) -> Option<glam::Mat4> {
+    if !state.shadows.enabled {
+        return None;
+    }
+    let light = state.lights.directional.first()?;
+    let bounds = state
+        .instances
+        .iter()
+        .filter(|(_, instance)| instance.is_visible_in_tree())
+        .fold(Bounds::EMPTY, |bounds, (_, instance)| {
+            bounds.union(&instance.bounds())
+        });
+    if bounds.is_empty() {
+        return None;
+    }
+    Some(crate::shadows::light_view_proj(light.direction, &bounds))
 }
 
 pub trait DrawModel<'a> {
-    fn draw_mesh(&mut self, mesh: &'a crate::models::Mesh, material: &'a crate::models::Material);
+    fn draw_mesh(&mut self, mesh: &'a crate::models::Mesh, material: &'a Material);
     fn draw_mesh_instanced(
         &mut self,
         mesh: &'a crate::models::Mesh,
         instances: Range<u32>,
-        material: &'a crate::models::Material,
+        material: &'a Material,
     );
     fn draw_model(&mut self, model: &'a Model);
     fn draw_model_instanced(&mut self, model: &'a Model, instances: Range<u32>);
+    // Only draws the meshes whose material fits the pipeline that is currently set.
+    fn draw_model_instanced_where(
+        &mut self,
+        model: &'a Model,
+        instances: Range<u32>,
+        filter: impl Fn(&Material) -> bool,
+    );
 }
 
 impl<'a, 'b> DrawModel<'b> for wgpu::RenderPass<'a>
 where
     'b: 'a,
 {
-    fn draw_mesh(&mut self, mesh: &'b crate::models::Mesh, material: &'a crate::models::Material) {
+    fn draw_mesh(&mut self, mesh: &'b crate::models::Mesh, material: &'b Material) {
         self.draw_mesh_instanced(mesh, 0..1, material)
     }
 
@@ -80,11 +281,11 @@ where
         &mut self,
         mesh: &'b crate::models::Mesh,
         instances: Range<u32>,
-        material: &'b crate::models::Material,
+        material: &'b Material,
     ) {
         self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
         self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-        self.set_bind_group(1, &material.bind_group, &[]);
+        self.set_bind_group(1, material.bind_group(), &[]);
         self.draw_indexed(0..mesh.num_elements, 0, instances);
     }
 
@@ -93,9 +294,20 @@ where
     }
 
     fn draw_model_instanced(&mut self, model: &'b Model, instances: Range<u32>) {
+        self.draw_model_instanced_where(model, instances, |_| true);
+    }
+
+    fn draw_model_instanced_where(
+        &mut self,
+        model: &'b Model,
+        instances: Range<u32>,
+        filter: impl Fn(&Material) -> bool,
+    ) {
         for mesh in &model.mesh {
             let material = &model.material[mesh.material];
-            self.draw_mesh_instanced(mesh, instances.clone(), material);
+            if filter(material) {
+                self.draw_mesh_instanced(mesh, instances.clone(), material);
+            }
         }
     }
 }
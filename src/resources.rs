@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+pub trait ResourceResolver {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    // Paths that read the same resource must map to the same key, so caches can share it.
+    fn cache_key(&self, path: &Path) -> PathBuf {
+        normalize(path)
+    }
+}
+
+impl<F> ResourceResolver for F
+where
+    F: Fn(&Path) -> io::Result<Vec<u8>>,
+{
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self(path)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FileResolver {
+    root: PathBuf,
+}
+
+impl FileResolver {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FileResolver { root: root.into() }
+    }
+}
+
+impl ResourceResolver for FileResolver {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(self.root.join(path))
+    }
+
+    // Symlinks and relative paths resolve to the same file on disk.
+    fn cache_key(&self, path: &Path) -> PathBuf {
+        let path = self.root.join(path);
+        path.canonicalize().unwrap_or_else(|_| normalize(&path))
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MemoryResolver {
+    files: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl MemoryResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, path: impl AsRef<Path>, bytes: impl Into<Vec<u8>>) -> Self {
+        self.insert(path, bytes);
+        self
+    }
+
+    pub fn insert(&mut self, path: impl AsRef<Path>, bytes: impl Into<Vec<u8>>) {
+        self.files.insert(normalize(path.as_ref()), bytes.into());
+    }
+
+    pub fn remove(&mut self, path: impl AsRef<Path>) -> Option<Vec<u8>> {
+        self.files.remove(&normalize(path.as_ref()))
+    }
+
+    pub fn contains(&self, path: impl AsRef<Path>) -> bool {
+        self.files.contains_key(&normalize(path.as_ref()))
+    }
+}
+
+impl ResourceResolver for MemoryResolver {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files.get(&normalize(path)).cloned().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not in memory", path.display()),
+            )
+        })
+    }
+}
+
+// Relative lookups like "models/../textures/a.png" must hit the same key as "textures/a.png".
+// Backslashes separate directories too, so paths written on Windows find the same files.
+fn normalize(path: &Path) -> PathBuf {
+    let path = PathBuf::from(path.to_string_lossy().replace('\\', "/"));
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                _ => normalized.push(".."),
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_dot_components() {
+        assert_eq!(normalize(Path::new("./a/./b.png")), Path::new("a/b.png"));
+        assert_eq!(
+            normalize(Path::new("models/../textures/a.png")),
+            Path::new("textures/a.png")
+        );
+        assert_eq!(normalize(Path::new("a/b/../../c.png")), Path::new("c.png"));
+    }
+
+    #[test]
+    fn keeps_parent_components_above_the_root() {
+        assert_eq!(normalize(Path::new("../a.png")), Path::new("../a.png"));
+        assert_eq!(normalize(Path::new("a/../../b.png")), Path::new("../b.png"));
+    }
+
+    #[test]
+    fn treats_backslashes_as_separators() {
+        assert_eq!(
+            normalize(Path::new("models\\..\\textures\\a.png")),
+            Path::new("textures/a.png")
+        );
+    }
+
+    #[test]
+    fn keys_memory_files_without_the_filesystem() {
+        let resolver = MemoryResolver::new();
+        // Exists on disk, but must not be canonicalized into an absolute path.
+        assert_eq!(
+            resolver.cache_key(Path::new("./Cargo.toml")),
+            Path::new("Cargo.toml")
+        );
+        assert_eq!(
+            resolver.cache_key(Path::new("models\\..\\a.obj")),
+            resolver.cache_key(Path::new("a.obj"))
+        );
+    }
+
+    #[test]
+    fn keys_files_by_their_location_on_disk() {
+        let resolver = FileResolver::default();
+        let key = resolver.cache_key(Path::new("src/../Cargo.toml"));
+        assert!(key.is_absolute());
+        assert_eq!(key, resolver.cache_key(Path::new("./Cargo.toml")));
+        assert_eq!(
+            FileResolver::new("src").cache_key(Path::new("../Cargo.toml")),
+            key
+        );
+    }
+
+    #[test]
+    fn finds_files_through_equivalent_paths() {
+        let resolver = MemoryResolver::new().with("textures/a.png", [1, 2, 3]);
+        assert_eq!(
+            resolver
+                .read(Path::new("models/../textures/a.png"))
+                .unwrap(),
+            [1, 2, 3]
+        );
+        assert_eq!(
+            resolver.read(Path::new("textures\\a.png")).unwrap(),
+            [1, 2, 3]
+        );
+        assert_eq!(
+            resolver.read(Path::new("a.png")).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+}
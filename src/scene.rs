@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::instances::camera::CameraMode;
+use crate::instances::{Instance, InstanceId, Transform};
+use crate::lights::Lights;
+use crate::models::Model;
+use crate::shadows::ShadowSettings;
+use crate::state::State;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scene {
+    pub clear_color: [f64; 4],
+    pub camera: SceneCamera,
+    pub lights: Lights,
+    pub shadows: ShadowSettings,
+    pub nodes: Vec<SceneNode>,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Scene {
+            clear_color: [1.0, 1.0, 1.0, 1.0],
+            camera: SceneCamera::default(),
+            lights: Lights::default(),
+            shadows: ShadowSettings::default(),
+            nodes: Vec::new(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneCamera {
+    pub position: Vec3,
+    pub target: Vec3,
+    pub fov: f32,
+}
+
+impl Default for SceneCamera {
+    fn default() -> Self {
+        SceneCamera {
+            position: Vec3::new(6.0, 2.0, -6.0),
+            target: Vec3::ZERO,
+            fov: 45.5,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneNode {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // Relative paths are resolved against the directory of the scene file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub transform: Transform,
+    pub visible: bool,
+    pub casts_shadows: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<SceneNode>,
+}
+
+impl Default for SceneNode {
+    fn default() -> Self {
+        SceneNode {
+            name: None,
+            model: None,
+            transform: Transform::IDENTITY,
+            visible: true,
+            casts_shadows: true,
+            children: Vec::new(),
+        }
+    }
+}
+
+impl Scene {
+    pub fn load(path: impl AsRef<Path>, state: &mut State) -> Result<Self> {
+        let path = path.as_ref();
+        let scene = Self::read(path)?;
+        scene.apply(state, path.parent().unwrap_or(Path::new("")))?;
+        Ok(scene)
+    }
+
+    pub fn save(path: impl AsRef<Path>, state: &State) -> Result<Self> {
+        let path = path.as_ref();
+        let scene = Self::capture(state, path.parent().unwrap_or(Path::new("")));
+        scene.write(path)?;
+        Ok(scene)
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|source| Error::Io {
+            path: path.into(),
+            source,
+        })?;
+        ron::from_str(&text).map_err(|source| Error::SceneParse {
+            path: path.into(),
+            source,
+        })
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let text = self.to_ron().map_err(|source| Error::SceneWrite {
+            path: path.into(),
+            source,
+        })?;
+        std::fs::write(path, text).map_err(|source| Error::Io {
+            path: path.into(),
+            source,
+        })
+    }
+
+    fn to_ron(&self) -> std::result::Result<String, ron::Error> {
+        // Lets hand-written files use `model: "cube.obj"` instead of `Some("cube.obj")`.
+        let config = ron::ser::PrettyConfig::default()
+            .extensions(ron::extensions::Extensions::IMPLICIT_SOME);
+        ron::ser::to_string_pretty(self, config)
+    }
+
+    // Replaces the instances, camera, lights, shadow settings and clear color of the state.
+    // Every model is loaded before anything is changed, so a failed load leaves the state as it was.
+    pub fn apply(&self, state: &mut State, base: &Path) -> Result<()> {
+        let mut models = HashMap::new();
+        let mut pending = self.nodes.iter().collect::<Vec<_>>();
+        while let Some(node) = pending.pop() {
+            if let Some(model) = &node.model {
+                if !models.contains_key(model) {
+                    let path = base.join(model);
+                    models.insert(model.clone(), state.load_model(&path.to_string_lossy())?);
+                }
+            }
+            pending.extend(&node.children);
+        }
+
+        state.instances.clear();
+        state.selected = None;
+        for node in &self.nodes {
+            add_node(state, &models, node, None);
+        }
+        state.instances.update_world_transforms();
+
+        let [r, g, b, a] = self.clear_color;
+        state.clear_color = wgpu::Color { r, g, b, a };
+        state.lights = self.lights.clone();
+        state.shadows = self.shadows;
+
+        let camera = &mut state.camera;
+        camera.position = self.camera.position;
+        camera.target = self.camera.target;
+        camera.fov = self.camera.fov;
+        // Points a fly camera at the new target.
+        camera.reset_mode(camera.mode);
+        Ok(())
+    }
+
+    // Instances whose model did not come from a file are saved without one.
+    pub fn capture(state: &State, base: &Path) -> Self {
+        let base = base.canonicalize().unwrap_or_else(|_| base.to_path_buf());
+        let nodes = state
+            .instances
+            .roots()
+            .map(|id| capture_node(state, &base, id))
+            .collect();
+
+        let camera = &state.camera;
+        let target = match camera.mode {
+            CameraMode::LookAt => camera.target,
+            CameraMode::Fly => camera.position + camera.forward() * camera.distance().max(1.0),
+        };
+        let color = state.clear_color;
+        Scene {
+            clear_color: [color.r, color.g, color.b, color.a],
+            camera: SceneCamera {
+                position: camera.position,
+                target,
+                fov: camera.fov,
+            },
+            lights: state.lights.clone(),
+            shadows: state.shadows,
+            nodes,
+        }
+    }
+}
+
+fn add_node(
+    state: &mut State,
+    models: &HashMap<String, Arc<Model>>,
+    node: &SceneNode,
+    parent: Option<InstanceId>,
+) {
+    let mut instance = match node.model.as_ref().and_then(|model| models.get(model)) {
+        Some(model) => Instance::new(model.clone(), node.transform),
+        None => Instance::empty(node.transform),
+    };
+    if let Some(name) = &node.name {
+        instance.set_name(name.clone());
+    }
+    instance.set_visible(node.visible);
+    instance.set_casts_shadows(node.casts_shadows);
+
+    let id = state.instances.insert(instance);
+    if parent.is_some() {
+        state.instances.set_parent(id, parent);
+    }
+    for child in &node.children {
+        add_node(state, models, child, Some(id));
+    }
+}
+
+fn capture_node(state: &State, base: &Path, id: InstanceId) -> SceneNode {
+    let instance = state
+        .instances
+        .get(id)
+        .expect("ids come from the live instance tree");
+    let model = instance
+        .model
+        .as_ref()
+        .and_then(|model| state.assets.model_path(model))
+        .map(|path| relative_to(path, base).to_string_lossy().replace('\\', "/"));
+
+    SceneNode {
+        name: instance.name().map(str::to_string),
+        model,
+        transform: instance.transform(),
+        visible: instance.is_visible(),
+        casts_shadows: instance.casts_shadows(),
+        children: instance
+            .children()
+            .iter()
+            .map(|&child| capture_node(state, base, child))
+            .collect(),
+    }
+}
+
+fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    path.strip_prefix(base)
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lights::DirectionalLight;
+    use crate::state::headless_test_state;
+
+    fn scene() -> Scene {
+        let lights = Lights {
+            directional: vec![DirectionalLight::new(
+                Vec3::new(-1.0, -2.0, 0.5),
+                Vec3::ONE,
+                2.0,
+            )],
+            ..Default::default()
+        };
+        Scene {
+            clear_color: [0.1, 0.2, 0.3, 1.0],
+            camera: SceneCamera {
+                position: Vec3::new(0.0, 3.0, 8.0),
+                target: Vec3::new(0.0, 1.0, 0.0),
+                fov: 60.0,
+            },
+            lights,
+            shadows: ShadowSettings {
+                resolution: 512,
+                ..Default::default()
+            },
+            nodes: vec![SceneNode {
+                name: Some("root".to_string()),
+                transform: Transform::from_translation(Vec3::X),
+                children: vec![SceneNode {
+                    name: Some("hidden child".to_string()),
+                    visible: false,
+                    casts_shadows: false,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn round_trips_through_ron() {
+        let scene = scene();
+        let text = scene.to_ron().unwrap();
+        assert_eq!(ron::from_str::<Scene>(&text).unwrap(), scene);
+    }
+
+    #[test]
+    fn reads_omitted_fields_as_defaults() {
+        let scene =
+            ron::from_str::<Scene>("#![enable(implicit_some)]\n(nodes: [(model: \"cube.obj\")])")
+                .unwrap();
+        assert_eq!(scene.camera, SceneCamera::default());
+        assert_eq!(scene.nodes[0].model.as_deref(), Some("cube.obj"));
+        assert!(scene.nodes[0].visible);
+    }
+
+    #[test]
+    fn round_trips_through_a_state() {
+        let Some(mut state) = headless_test_state() else {
+            return;
+        };
+        let scene = scene();
+        scene.apply(&mut state, Path::new("")).unwrap();
+
+        let captured = Scene::capture(&state, Path::new(""));
+        let text = captured.to_ron().unwrap();
+        let read = ron::from_str::<Scene>(&text).unwrap();
+        assert_eq!(read, scene);
+
+        let mut other = headless_test_state().unwrap();
+        read.apply(&mut other, Path::new("")).unwrap();
+        assert_eq!(Scene::capture(&other, Path::new("")), scene);
+        assert!(!other
+            .instances
+            .get(other.instances.find("hidden child").unwrap())
+            .unwrap()
+            .is_visible_in_tree());
+    }
+
+    #[test]
+    fn points_fly_cameras_at_the_loaded_target() {
+        let Some(mut state) = headless_test_state() else {
+            return;
+        };
+        state.camera.reset_mode(CameraMode::Fly);
+        state.camera.rotate(90.0, -30.0);
+        let scene = scene();
+        scene.apply(&mut state, Path::new("")).unwrap();
+
+        let expected = (scene.camera.target - scene.camera.position).normalize();
+        assert_eq!(state.camera.mode, CameraMode::Fly);
+        assert!(state.camera.forward().abs_diff_eq(expected, 1e-5));
+    }
+}
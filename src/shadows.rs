@@ -0,0 +1,314 @@
+use glam::{Mat4, Vec3};
+
+use crate::models::Bounds;
+use crate::texture::Texture;
+
+// Only the first directional light casts shadows.
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ShadowSettings {
+    pub enabled: bool,
+    pub resolution: u32,
+    // Subtracted from the depth in the light's clip space before comparing, against acne.
+    pub bias: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings {
+            enabled: true,
+            resolution: 2048,
+            bias: 0.002,
+        }
+    }
+}
+
+impl ShadowSettings {
+    // Disabled shadows still need a texture to bind, but a single texel is enough.
+    pub fn map_resolution(&self) -> u32 {
+        if self.enabled {
+            self.resolution
+        } else {
+            1
+        }
+    }
+}
+
+// Fits an orthographic view along the light direction around the bounds.
+pub fn light_view_proj(direction: Vec3, bounds: &Bounds) -> Mat4 {
+    let direction = match direction.try_normalize() {
+        Some(direction) => direction,
+        None => Vec3::NEG_Y,
+    };
+    let center = bounds.center();
+    let radius = bounds.radius().max(1e-3);
+    let up = if direction.y.abs() > 0.99 {
+        Vec3::Z
+    } else {
+        Vec3::Y
+    };
+
+    let view = Mat4::look_at_rh(center - direction * radius * 2.0, center, up);
+    let proj = Mat4::orthographic_rh(-radius, radius, -radius, radius, radius, radius * 3.0);
+    proj * view
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadowUniform {
+    view_proj: [[f32; 4]; 4],
+    bias: f32,
+    texel_size: f32,
+    enabled: u32,
+    _padding: u32,
+}
+
+impl ShadowUniform {
+    pub fn new(view_proj: Mat4, settings: &ShadowSettings, resolution: u32) -> Self {
+        ShadowUniform {
+            view_proj: view_proj.to_cols_array_2d(),
+            bias: settings.bias,
+            texel_size: 1.0 / resolution as f32,
+            enabled: 1,
+            _padding: 0,
+        }
+    }
+
+    pub fn disabled() -> Self {
+        ShadowUniform {
+            view_proj: Mat4::IDENTITY.to_cols_array_2d(),
+            bias: 0.0,
+            texel_size: 0.0,
+            enabled: 0,
+            _padding: 0,
+        }
+    }
+}
+
+pub(crate) struct ShadowMap {
+    pub texture: Texture,
+    pub resolution: u32,
+    pub buffer: wgpu::Buffer,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    // Read by the main pass.
+    pub bind_group: wgpu::BindGroup,
+    // Holds the light's view for the shadow pass itself.
+    pub pass_bind_group: wgpu::BindGroup,
+    pub pipeline: wgpu::RenderPipeline,
+}
+
+impl ShadowMap {
+    pub fn new(device: &wgpu::Device, resolution: u32) -> Self {
+        let resolution = clamp_resolution(device, resolution);
+        let texture = Texture::create_shadow_map(resolution, device);
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow Buffer"),
+            size: std::mem::size_of::<ShadowUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Shadow Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = create_bind_group(device, &bind_group_layout, &texture, &buffer);
+
+        let pass_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Shadow Pass Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let pass_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shadow Pass Bind Group"),
+            layout: &pass_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/shadow.wgsl"));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            bind_group_layouts: &[&pass_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs",
+                compilation_options: Default::default(),
+                buffers: &[
+                    crate::models::Vertex::desc(),
+                    crate::instances::InstanceRaw::desc(),
+                ],
+            },
+            fragment: None,
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                // Pushes steep surfaces further back than the constant bias alone.
+                bias: wgpu::DepthBiasState {
+                    constant: 0,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multiview: None,
+            multisample: wgpu::MultisampleState::default(),
+        });
+
+        ShadowMap {
+            texture,
+            resolution,
+            buffer,
+            bind_group_layout,
+            bind_group,
+            pass_bind_group,
+            pipeline,
+        }
+    }
+
+    pub fn set_resolution(&mut self, device: &wgpu::Device, resolution: u32) {
+        let resolution = clamp_resolution(device, resolution);
+        if resolution == self.resolution {
+            return;
+        }
+
+        self.texture = Texture::create_shadow_map(resolution, device);
+        self.bind_group =
+            create_bind_group(device, &self.bind_group_layout, &self.texture, &self.buffer);
+        self.resolution = resolution;
+    }
+}
+
+fn clamp_resolution(device: &wgpu::Device, resolution: u32) -> u32 {
+    resolution.clamp(1, device.limits().max_texture_dimension_2d)
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    texture: &Texture,
+    buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Shadow Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: buffer.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corners(bounds: &Bounds) -> impl Iterator<Item = Vec3> + '_ {
+        (0..8).map(|i| {
+            Vec3::select(
+                glam::BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0),
+                bounds.max,
+                bounds.min,
+            )
+        })
+    }
+
+    #[test]
+    fn keeps_bounds_inside_the_light_view() {
+        let bounds = Bounds::from_points([Vec3::new(-3.0, 0.0, -1.0), Vec3::new(5.0, 2.0, 4.0)]);
+        let directions = [
+            Vec3::new(-1.0, -2.0, 0.5),
+            Vec3::NEG_Y,
+            Vec3::Y,
+            Vec3::X,
+            Vec3::new(0.3, -0.1, -1.0),
+            // Falls back to shining straight down.
+            Vec3::ZERO,
+        ];
+        for direction in directions {
+            let view_proj = light_view_proj(direction, &bounds);
+            for corner in corners(&bounds) {
+                let ndc = view_proj.project_point3(corner);
+                assert!(
+                    ndc.x.abs() <= 1.0 + 1e-5 && ndc.y.abs() <= 1.0 + 1e-5,
+                    "{} is outside the light view along {}",
+                    corner,
+                    direction
+                );
+                assert!(
+                    (-1e-5..=1.0 + 1e-5).contains(&ndc.z),
+                    "{} is outside the light depth range along {}",
+                    corner,
+                    direction
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn uses_a_single_texel_when_disabled() {
+        let mut settings = ShadowSettings::default();
+        assert_eq!(settings.map_resolution(), settings.resolution);
+        settings.enabled = false;
+        assert_eq!(settings.map_resolution(), 1);
+    }
+}
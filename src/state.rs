@@ -1,69 +1,151 @@
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
 
 use wgpu::util::DeviceExt;
 
 use crate::texture;
 
 pub struct State {
-    pub window: Arc<winit::window::Window>,
+    pub window: Option<Arc<winit::window::Window>>,
     pub instance: wgpu::Instance,
-    pub surface: wgpu::Surface<'static>,
+    pub surface: Option<wgpu::Surface<'static>>,
     pub adapter: wgpu::Adapter,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub render_pipeline: wgpu::RenderPipeline,
+    pub pbr_pipeline: wgpu::RenderPipeline,
     pub surface_config: wgpu::SurfaceConfiguration,
     pub camera: crate::instances::camera::Camera,
-    pub instances: Vec<crate::instances::Instance>,
+    pub controller: Box<dyn crate::controllers::CameraController>,
+    pub selected: Option<crate::instances::InstanceId>,
+    pub input: crate::input::InputState,
+    pub delta_time: f32,
+    pub last_frame: Instant,
+    pub instances: crate::instances::Instances,
+    pub assets: crate::assets::AssetCache,
     pub camera_buffer: wgpu::Buffer,
     pub camera_uniform: crate::instances::camera::CameraUniform,
     pub camera_bind_group: wgpu::BindGroup,
+    pub lights: crate::lights::Lights,
+    pub light_buffer: wgpu::Buffer,
+    pub light_bind_group: wgpu::BindGroup,
+    pub(crate) light_bind_group_layout: wgpu::BindGroupLayout,
+    pub environment: texture::Texture,
+    pub shadows: crate::shadows::ShadowSettings,
+    pub(crate) shadow_map: crate::shadows::ShadowMap,
     pub depth_texture: texture::Texture,
-    pub f: fn(&mut State) -> (),
+    pub msaa_texture: Option<texture::Texture>,
+    pub sample_count: u32,
+    pub clear_color: wgpu::Color,
+    pub instance_buffer: wgpu::Buffer,
+    pub screenshot_path: Option<PathBuf>,
 }
 
 impl State {
-    pub fn new(window: winit::window::Window, f: fn(&mut State) -> ()) -> Self {
+    pub fn new(
+        window: winit::window::Window,
+        config: &crate::config::Config,
+    ) -> crate::Result<Self> {
         let window = Arc::new(window);
         let window_size = window.inner_size();
-        let instance = wgpu::Instance::default();
+        let instance = config.create_instance();
 
-        let surface = instance.create_surface(window.clone()).unwrap();
-        let adapter = create_adapter(&instance, &surface);
-        let surface_config = surface
-            .get_default_config(&adapter, window_size.width, window_size.height)
-            .unwrap();
+        let surface = instance
+            .create_surface(window.clone())
+            .map_err(crate::Error::CreateSurface)?;
+        let adapter = create_adapter(&instance, Some(&surface), config)?;
+        let mut surface_config = surface
+            .get_default_config(
+                &adapter,
+                window_size.width.max(1),
+                window_size.height.max(1),
+            )
+            .ok_or(crate::Error::UnsupportedSurface)?;
+        let capabilities = surface.get_capabilities(&adapter);
+        if capabilities.usages.contains(wgpu::TextureUsages::COPY_SRC) {
+            surface_config.usage |= wgpu::TextureUsages::COPY_SRC;
+        }
+        surface_config.present_mode = match config.present_mode {
+            mode @ (wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync) => mode,
+            mode if capabilities.present_modes.contains(&mode) => mode,
+            _ => wgpu::PresentMode::Fifo,
+        };
 
-        let (device, queue) = create_device(&adapter);
+        let (device, queue) = create_device(&adapter)?;
 
         surface.configure(&device, &surface_config);
 
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Texture Bind Group Layout"),
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            multisampled: false,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-            });
+        Ok(Self::from_parts(
+            Some((window, surface)),
+            instance,
+            adapter,
+            device,
+            queue,
+            surface_config,
+            config,
+        ))
+    }
+
+    pub fn new_headless(
+        width: u32,
+        height: u32,
+        config: &crate::config::Config,
+    ) -> crate::Result<Self> {
+        if width == 0 || height == 0 {
+            return Err(crate::Error::InvalidSize { width, height });
+        }
+        let instance = config.create_instance();
+        let adapter = create_adapter(&instance, None, config)?;
+        let (device, queue) = create_device(&adapter)?;
+
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: Vec::new(),
+        };
+
+        Ok(Self::from_parts(
+            None,
+            instance,
+            adapter,
+            device,
+            queue,
+            surface_config,
+            config,
+        ))
+    }
+
+    fn from_parts(
+        target: Option<(Arc<winit::window::Window>, wgpu::Surface<'static>)>,
+        instance: wgpu::Instance,
+        adapter: wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        surface_config: wgpu::SurfaceConfiguration,
+        config: &crate::config::Config,
+    ) -> Self {
+        let (window, surface) = target.unzip();
+
+        let material_bind_group_layout = crate::models::PhongMaterial::bind_group_layout(&device);
+        let pbr_material_bind_group_layout = crate::models::PbrMaterial::bind_group_layout(&device);
 
         let shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/shader.wgsl"));
+        let pbr_shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/pbr.wgsl"));
+        let sample_count = supported_sample_count(
+            &adapter,
+            &device,
+            surface_config.format,
+            config.sample_count,
+        );
 
-        let camera = crate::instances::camera::Camera::new(&window);
+        let camera =
+            crate::instances::camera::Camera::new(surface_config.width, surface_config.height);
         let camera_uniform = crate::instances::camera::CameraUniform::default();
 
         let camera_buffer = create_buffer_init(
@@ -78,7 +160,7 @@ impl State {
                 label: Some("Camera Bind Group Layout"),
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     count: None,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
@@ -97,48 +179,94 @@ impl State {
             }],
         });
 
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&camera_bind_group_layout, &texture_bind_group_layout],
-                push_constant_ranges: &[],
+        let lights = crate::lights::Lights::default();
+
+        let light_buffer = create_buffer_init(
+            &device,
+            Some("Light Buffer"),
+            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            bytemuck::cast_slice(&[lights.to_uniform()]),
+        );
+
+        let light_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Light Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        count: None,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        count: None,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        count: None,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    },
+                ],
             });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs",
-                compilation_options: Default::default(),
-                buffers: &[crate::models::Vertex::desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs",
-                compilation_options: Default::default(),
-                targets: &[Some(wgpu::ColorTargetState {
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    format: surface_config.format,
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                cull_mode: None,
-                ..Default::default()
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multiview: None,
-            multisample: wgpu::MultisampleState::default(),
-        });
+        let environment = create_environment_texture(
+            &device,
+            &queue,
+            &image::DynamicImage::ImageRgba8(crate::lights::default_environment()),
+        );
+        let light_bind_group = create_light_bind_group(
+            &device,
+            &light_bind_group_layout,
+            &light_buffer,
+            &environment,
+        );
+
+        let shadow_map = crate::shadows::ShadowMap::new(&device, config.shadows.map_resolution());
+
+        let render_pipeline = create_render_pipeline(
+            &device,
+            "Render Pipeline",
+            &[
+                &camera_bind_group_layout,
+                &material_bind_group_layout,
+                &light_bind_group_layout,
+                &shadow_map.bind_group_layout,
+            ],
+            &shader,
+            surface_config.format,
+            sample_count,
+        );
+        let pbr_pipeline = create_render_pipeline(
+            &device,
+            "PBR Pipeline",
+            &[
+                &camera_bind_group_layout,
+                &pbr_material_bind_group_layout,
+                &light_bind_group_layout,
+                &shadow_map.bind_group_layout,
+            ],
+            &pbr_shader,
+            surface_config.format,
+            sample_count,
+        );
 
-        let depth_texture = texture::Texture::create_depth_texture(&surface_config, &device);
+        let depth_texture =
+            texture::Texture::create_depth_texture(&surface_config, sample_count, &device);
+        let msaa_texture = (sample_count > 1)
+            .then(|| texture::Texture::create_msaa_texture(&surface_config, sample_count, &device));
+        let instance_buffer = create_instance_buffer(&device, INITIAL_INSTANCE_CAPACITY);
 
         State {
             window,
@@ -148,34 +276,381 @@ impl State {
             device,
             queue,
             render_pipeline,
+            pbr_pipeline,
             surface_config,
             camera,
+            controller: Box::new(crate::controllers::OrbitController::default()),
+            selected: None,
+            input: crate::input::InputState::default(),
+            delta_time: 0.0,
+            last_frame: Instant::now(),
             camera_buffer,
             camera_uniform,
             camera_bind_group,
-            instances: Vec::new(),
+            lights,
+            light_buffer,
+            light_bind_group,
+            light_bind_group_layout,
+            environment,
+            shadows: config.shadows,
+            shadow_map,
+            instances: crate::instances::Instances::new(),
+            assets: crate::assets::AssetCache::new(),
             depth_texture,
-            f,
+            msaa_texture,
+            sample_count,
+            clear_color: config.clear_color,
+            instance_buffer,
+            screenshot_path: None,
         }
     }
 
-    pub fn update(&mut self) {
-        (self.f)(self);
+    pub(crate) fn begin_frame(&mut self) {
+        let now = Instant::now();
+        self.delta_time = now.duration_since(self.last_frame).as_secs_f32();
+        self.last_frame = now;
+
+        self.controller
+            .update(&mut self.camera, &self.input, self.delta_time);
+    }
+
+    pub(crate) fn end_frame(&mut self) {
+        self.input.end_frame();
     }
 
-    pub fn add_instance(&mut self, mesh_path: &str, position: glam::Vec3) {
-        let instance = crate::instances::Instance::new(mesh_path, self, position);
-        self.instances.push(instance);
+    pub fn is_minimized(&self) -> bool {
+        self.window.as_ref().is_some_and(|window| {
+            let size = window.inner_size();
+            size.width == 0 || size.height == 0
+        })
     }
+
+    // Minimized windows report a zero size, which a surface cannot be configured with.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.surface_config.width = width;
+        self.surface_config.height = height;
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.surface_config);
+        }
+        self.depth_texture = texture::Texture::create_depth_texture(
+            &self.surface_config,
+            self.sample_count,
+            &self.device,
+        );
+        if self.msaa_texture.is_some() {
+            self.msaa_texture = Some(texture::Texture::create_msaa_texture(
+                &self.surface_config,
+                self.sample_count,
+                &self.device,
+            ));
+        }
+        self.camera.reconfigure_aspect_ratio(width, height);
+    }
+
+    pub fn set_camera_controller(
+        &mut self,
+        mut controller: Box<dyn crate::controllers::CameraController>,
+    ) {
+        controller.activate(&mut self.camera);
+        if let Some(window) = &self.window {
+            crate::input::grab_cursor(window, controller.grabs_cursor());
+        }
+        self.controller = controller;
+    }
+
+    pub fn frame_selected(&mut self) {
+        self.instances.update_world_transforms();
+        let bounds = match self.selected.and_then(|id| self.instances.get(id)) {
+            Some(instance) => instance.bounds(),
+            None => self
+                .instances
+                .iter()
+                .filter(|(_, instance)| instance.is_visible_in_tree())
+                .fold(crate::models::Bounds::EMPTY, |bounds, (_, instance)| {
+                    bounds.union(&instance.bounds())
+                }),
+        };
+        if !bounds.is_empty() {
+            self.controller.frame(&mut self.camera, bounds);
+        }
+    }
+
+    // Lights PBR materials from an equirectangular image, scaled by `lights.environment_intensity`.
+    pub fn set_environment(&mut self, image: &image::DynamicImage) {
+        self.environment = create_environment_texture(&self.device, &self.queue, image);
+        self.light_bind_group = create_light_bind_group(
+            &self.device,
+            &self.light_bind_group_layout,
+            &self.light_buffer,
+            &self.environment,
+        );
+    }
+
+    pub fn load_environment(&mut self, path: impl AsRef<Path>) -> crate::Result<()> {
+        self.load_environment_with(path, &crate::resources::FileResolver::default())
+    }
+
+    pub fn load_environment_with(
+        &mut self,
+        path: impl AsRef<Path>,
+        resolver: &dyn crate::resources::ResourceResolver,
+    ) -> crate::Result<()> {
+        let bytes = crate::models::read_resource(resolver, path.as_ref())?;
+        self.set_environment(&image::load_from_memory(&bytes)?);
+        Ok(())
+    }
+
+    pub fn render_image(&mut self) -> crate::Result<image::RgbaImage> {
+        self.instances.update_world_transforms();
+        crate::renderer::draw_to_image(self)
+    }
+
+    // Renders the scene again offscreen rather than reading back the presented frame, so it works
+    // headless too. Use `request_screenshot` to save the next frame that reaches the window.
+    pub fn capture_screenshot(&mut self) -> crate::Result<image::RgbaImage> {
+        self.render_image()
+    }
+
+    // Saved after the next frame is drawn, read back from the surface when it allows copies.
+    pub fn request_screenshot(&mut self, path: impl Into<PathBuf>) {
+        self.screenshot_path = Some(path.into());
+    }
+
+    pub fn write_instance_buffer(&mut self, instances: &[crate::instances::InstanceRaw]) {
+        let data: &[u8] = bytemuck::cast_slice(instances);
+        if data.len() as wgpu::BufferAddress > self.instance_buffer.size() {
+            self.instance_buffer =
+                create_instance_buffer(&self.device, instances.len().next_power_of_two());
+        }
+        self.queue.write_buffer(&self.instance_buffer, 0, data);
+    }
+
+    pub fn load_model(&mut self, mesh_path: &str) -> crate::Result<Arc<crate::models::Model>> {
+        self.assets.load_model(mesh_path, &self.device, &self.queue)
+    }
+
+    pub fn load_model_from_bytes(
+        &mut self,
+        mesh_path: &str,
+        bytes: &[u8],
+    ) -> crate::Result<Arc<crate::models::Model>> {
+        self.assets
+            .load_model_from_bytes(mesh_path, bytes, &self.device, &self.queue)
+    }
+
+    pub fn add_instance(
+        &mut self,
+        mesh_path: &str,
+        position: glam::Vec3,
+    ) -> crate::Result<crate::instances::InstanceId> {
+        let model = self.load_model(mesh_path)?;
+        Ok(self.add_model_instance(
+            model,
+            crate::instances::Transform::from_translation(position),
+        ))
+    }
+
+    pub fn add_model_instance(
+        &mut self,
+        model: Arc<crate::models::Model>,
+        transform: crate::instances::Transform,
+    ) -> crate::instances::InstanceId {
+        self.instances
+            .insert(crate::instances::Instance::new(model, transform))
+    }
+
+    pub fn add_group(
+        &mut self,
+        transform: crate::instances::Transform,
+    ) -> crate::instances::InstanceId {
+        self.instances
+            .insert(crate::instances::Instance::empty(transform))
+    }
+
+    pub fn set_parent(
+        &mut self,
+        child: crate::instances::InstanceId,
+        parent: Option<crate::instances::InstanceId>,
+    ) -> bool {
+        self.instances.set_parent(child, parent)
+    }
+
+    // Also removes the children; models are freed once no instance uses them, as the asset cache only holds weak references.
+    pub fn remove_instance(
+        &mut self,
+        id: crate::instances::InstanceId,
+    ) -> Option<crate::instances::Instance> {
+        let removed = self.instances.remove(id);
+        if self
+            .selected
+            .is_some_and(|selected| !self.instances.contains(selected))
+        {
+            self.selected = None;
+        }
+        removed
+    }
+
+    pub fn get_instance(
+        &self,
+        id: crate::instances::InstanceId,
+    ) -> Option<&crate::instances::Instance> {
+        self.instances.get(id)
+    }
+
+    pub fn get_instance_mut(
+        &mut self,
+        id: crate::instances::InstanceId,
+    ) -> Option<&mut crate::instances::Instance> {
+        self.instances.get_mut(id)
+    }
+
+    pub fn find_instance(&self, name: &str) -> Option<crate::instances::InstanceId> {
+        self.instances.find(name)
+    }
+}
+
+fn create_adapter(
+    instance: &wgpu::Instance,
+    surface: Option<&wgpu::Surface>,
+    config: &crate::config::Config,
+) -> crate::Result<wgpu::Adapter> {
+    use crate::config::AdapterFallback;
+
+    let request = |force_fallback_adapter| {
+        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: config.power_preference,
+            force_fallback_adapter,
+            compatible_surface: surface,
+        }))
+    };
+
+    let adapter = match config.adapter_fallback {
+        AdapterFallback::Auto => request(false).or_else(|| {
+            log::warn!("no hardware adapter found, retrying with a software adapter");
+            request(true)
+        }),
+        AdapterFallback::Never => request(false),
+        AdapterFallback::Always => request(true),
+    }
+    .ok_or(crate::Error::NoAdapter)?;
+
+    let info = adapter.get_info();
+    log::info!(
+        "using adapter {:?} ({:?}, {:?} backend)",
+        info.name,
+        info.device_type,
+        info.backend
+    );
+    Ok(adapter)
+}
+
+const INITIAL_INSTANCE_CAPACITY: usize = 16;
+
+fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Instance Buffer"),
+        size: (capacity * std::mem::size_of::<crate::instances::InstanceRaw>())
+            as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    label: &str,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(&format!("{} Layout", label)),
+        bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs",
+            compilation_options: Default::default(),
+            buffers: &[
+                crate::models::Vertex::desc(),
+                crate::instances::InstanceRaw::desc(),
+            ],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs",
+            compilation_options: Default::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                blend: Some(wgpu::BlendState::REPLACE),
+                format,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            cull_mode: None,
+            ..Default::default()
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multiview: None,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+    })
 }
 
-fn create_adapter(instance: &wgpu::Instance, surface: &wgpu::Surface) -> wgpu::Adapter {
-    pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-        power_preference: wgpu::PowerPreference::default(),
-        force_fallback_adapter: false,
-        compatible_surface: Some(surface),
-    }))
-    .unwrap()
+// An equirectangular image, wrapped around horizontally.
+fn create_environment_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    image: &image::DynamicImage,
+) -> texture::Texture {
+    let sampler = texture::SamplerOptions {
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        ..Default::default()
+    };
+    texture::Texture::from_image(device, queue, image, "Environment Texture", &sampler)
+}
+
+fn create_light_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    light_buffer: &wgpu::Buffer,
+    environment: &texture::Texture,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Light Bind group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: light_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&environment.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(&environment.sampler),
+            },
+        ],
+    })
 }
 
 fn create_buffer_init(
@@ -191,15 +666,61 @@ fn create_buffer_init(
     })
 }
 
-fn create_device(adapter: &wgpu::Adapter) -> (wgpu::Device, wgpu::Queue) {
+fn supported_sample_count(
+    adapter: &wgpu::Adapter,
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    requested: u32,
+) -> u32 {
+    // Without the adapter-specific feature, wgpu only accepts the guaranteed 1x and 4x.
+    let features = |format: wgpu::TextureFormat| {
+        if device
+            .features()
+            .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+        {
+            adapter.get_texture_format_features(format).flags
+        } else {
+            format.guaranteed_format_features(device.features()).flags
+        }
+    };
+    let color = features(format);
+    let depth = features(wgpu::TextureFormat::Depth32Float);
+    let sample_count = [8, 4, 2]
+        .into_iter()
+        .filter(|&count| count <= requested)
+        .find(|&count| color.sample_count_supported(count) && depth.sample_count_supported(count))
+        .unwrap_or(1);
+    if sample_count != requested {
+        log::warn!(
+            "{}x multisampling is not supported, using {}x",
+            requested,
+            sample_count
+        );
+    }
+    sample_count
+}
+
+fn create_device(adapter: &wgpu::Adapter) -> crate::Result<(wgpu::Device, wgpu::Queue)> {
     pollster::block_on(adapter.request_device(
         &wgpu::DeviceDescriptor {
             label: Some("Device"),
-            required_features: wgpu::Features::empty(),
+            // Lets multisampling use every sample count the adapter supports.
+            required_features: adapter.features()
+                & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
             required_limits:
                 wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
         },
         None,
     ))
-    .unwrap()
+    .map_err(crate::Error::RequestDevice)
+}
+
+// Adapters are optional on CI machines, so GPU-backed tests skip themselves without one.
+#[cfg(test)]
+pub(crate) fn headless_test_state() -> Option<State> {
+    match State::new_headless(64, 64, &crate::config::Config::default()) {
+        Ok(state) => Some(state),
+        Err(crate::Error::NoAdapter) => None,
+        Err(err) => panic!("failed to create a headless state: {}", err),
+    }
 }